@@ -18,6 +18,47 @@ if [[ -t 0 ]] && [[ "$_RHIS_LOADED" != "loaded" ]]; then
   export HISTCONTROL="${HISTCONTROL:-ignorespace}"
   # Append new history items to .bash_history
   shopt -s histappend
+  # Keep multi-line commands as one entry, newlines included
+  shopt -s cmdhist lithist
+
+  # with `rhis init --sync`, commands recorded on other machines are added
+  # to HISTFILE here, before bash reads it once the rc files are done
+  RHIS_SYNC=
+  if [[ -n $RHIS_SYNC ]]; then
+    HISTFILE=$HISTFILE $EXEUTABLE sync-histfile \
+      --known "$(compgen -A alias -A function -A builtin -A keyword)" >/dev/null
+  fi
+
+  # commands deleted from rhis are journaled; each session only looks at
+  # what got appended since it last checked, unless the first line says the
+  # journal was compacted since, which sends it back to the top.
+  RHIS_JOURNAL=~/.local/share/rhis/deleted
+  __rhis_forgotten=0
+  __rhis_generation=
+  if [[ -r $RHIS_JOURNAL ]]; then
+    mapfile -t __rhis_journal < "$RHIS_JOURNAL"
+    __rhis_generation=${__rhis_journal[0]}
+    __rhis_forgotten=${#__rhis_journal[@]}
+    unset __rhis_journal
+  fi
+
+  function rhis_forget {
+      [[ -r $RHIS_JOURNAL ]] || return 1
+      local generation digests
+      read -r generation < "$RHIS_JOURNAL"
+      if [[ $generation != "$__rhis_generation" ]]; then
+          __rhis_generation=$generation
+          __rhis_forgotten=1
+      fi
+      mapfile -t -s $__rhis_forgotten digests < "$RHIS_JOURNAL"
+      (( ${#digests[@]} )) || return 1
+      __rhis_forgotten=$(( __rhis_forgotten + ${#digests[@]} ))
+
+      local n
+      for n in $(HISTTIMEFORMAT= history | $EXEUTABLE forget "${digests[@]}"); do
+          history -d $n
+      done
+  }
 
   IDX=0
   function rhis_prompt_command {
@@ -33,11 +74,26 @@ if [[ -t 0 ]] && [[ "$_RHIS_LOADED" != "loaded" ]]; then
           then
               cmd="${cmd#* }"
               cmd="${cmd##*( )}"
-              HISTFILE=$HISTFILE $EXEUTABLE add --exit ${exit_code} "$cmd"
+              # only a "not found" needs to know what the session defines
+              local known=
+              if [ ${exit_code} -eq 127 ]
+              then
+                  known=$(compgen -A alias -A function -A builtin -A keyword)
+              fi
+              HISTFILE=$HISTFILE $EXEUTABLE add --exit ${exit_code} --known "$known" "$cmd"
           fi
           IDX=$i
       fi
 
+      # deleting renumbers the history, so pick up where it now ends
+      if rhis_forget
+      then
+          cmd=$(HISTTIMEFORMAT= history 1)
+          cmd="${cmd##*( )}"
+          IDX=${cmd/ */}
+          IDX=${IDX:-0}
+      fi
+
       return ${exit_code}
   }
 
@@ -49,14 +105,34 @@ if [[ -t 0 ]] && [[ "$_RHIS_LOADED" != "loaded" ]]; then
     PROMPT_COMMAND="rhis_prompt_command;${PROMPT_COMMAND#;}"
   fi
 
+  # the selection comes back on fd 3 with a trailing newline when it
+  # should run and a tab when it should only be edited; \C-x2 is rebound
+  # accordingly and fired right after rhis_search by the \C-r macro.
   function rhis_search {
       local cmd=${READLINE_LINE[@]};
-      READLINE_LINE= ;
-      HISTFILE=$HISTFILE $EXEUTABLE search --light --bottom "$cmd"
+      local out=
+      IFS= read -r -d '' out < <(HISTFILE=$HISTFILE $EXEUTABLE search --light --bottom --fd 3 "$cmd" 3>&1 1>/dev/tty)
+
+      bind '"\C-x2": redraw-current-line'
+      case "$out" in
+        *$'\n')
+          READLINE_LINE=${out%$'\n'}
+          bind '"\C-x2": accept-line'
+          ;;
+        *$'\t')
+          READLINE_LINE=${out%$'\t'}
+          ;;
+        *)
+          READLINE_LINE=
+          ;;
+      esac
+      READLINE_POINT=${#READLINE_LINE}
   }
 
   # If this is an interactive shell, take ownership of ctrl-r.
   if [[ $- =~ .*i.* ]]; then
-      bind -x '"\C-r": "rhis_search"'
+      bind -x '"\C-x1": "rhis_search"'
+      bind '"\C-x2": redraw-current-line'
+      bind '"\C-r": "\C-x1\C-x2"'
   fi
 fi
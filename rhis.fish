@@ -0,0 +1,52 @@
+if status is-interactive; and not set -q __rhis_loaded
+    set -g __rhis_loaded loaded
+
+    set -g __rhis_exeutable (command -v rhis)
+    if test -z "$__rhis_exeutable"
+        echo "rhis does not found"
+        return 1
+    end
+
+    # fish keeps its own history file, so rhis is never pointed at a HISTFILE
+    function rhis_postexec --on-event fish_postexec
+        set -l exit_code $status
+        set -l cmd $argv[1]
+        if test -n "$cmd"
+            # only a "not found" needs to know what the session defines
+            set -l known
+            if test $exit_code -eq 127
+                set known (functions -a -n) (builtin -n) (abbr --list)
+            end
+            HISTFILE= $__rhis_exeutable add --shell fish --exit $exit_code --duration $CMD_DURATION \
+                --known "$known" -- "$cmd"
+        end
+    end
+
+    # the selection comes back on fd 3 with a trailing newline when it
+    # should run and a tab when it should only be edited.
+    function rhis_search
+        set -l cmd (commandline | string collect)
+        set -l out (HISTFILE= $__rhis_exeutable search --light --bottom --fd 3 -- "$cmd" 3>&1 1>/dev/tty | string collect -N)
+
+        if test -z "$out"
+            commandline -r ""
+            commandline -f repaint
+            return
+        end
+
+        set -l run 0
+        if string match -qr '\n\z' -- "$out"
+            set run 1
+        end
+        commandline -r -- (string replace -r '[\n\t]\z' '' -- "$out" | string collect)
+        commandline -f repaint
+        if test $run = 1
+            commandline -f execute
+        end
+    end
+
+    bind \cr rhis_search
+    if bind -M insert >/dev/null 2>&1
+        bind -M insert \cr rhis_search
+    end
+end
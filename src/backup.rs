@@ -0,0 +1,126 @@
+use crate::{
+    db::{self, Record},
+    settings::Settings,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+// archives are json lines: this header, then one `Record` per line.
+// Version 2 records carry every spelling of their command; version 1
+// archives still restore, with `original` as the only spelling.
+const FORMAT: &str = "rhis-backup";
+const VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    created: i64,
+    commands: usize,
+}
+
+fn write_archive(path: &str, records: &[Record]) -> Result<(), String> {
+    let mut out = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    let header = Header {
+        format: FORMAT.into(),
+        version: VERSION,
+        created: db::now_secs(),
+        commands: records.len(),
+    };
+    serde_json::to_writer(&mut out, &header).map_err(|e| e.to_string())?;
+    writeln!(out).map_err(|e| e.to_string())?;
+    for r in records {
+        serde_json::to_writer(&mut out, r).map_err(|e| e.to_string())?;
+        writeln!(out).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+fn read_archive(path: &str) -> Result<Vec<Record>, String> {
+    let mut lines = BufReader::new(File::open(path).map_err(|e| e.to_string())?).lines();
+    let first = lines.next().ok_or("empty archive")?.map_err(|e| e.to_string())?;
+    let header: Header = serde_json::from_str(&first).map_err(|_| "not a rhis backup")?;
+    if header.format != FORMAT {
+        return Err("not a rhis backup".into());
+    }
+    if header.version > VERSION {
+        return Err(format!("archive version {} is newer than supported {VERSION}", header.version));
+    }
+
+    let mut records = Vec::with_capacity(header.commands);
+    for (n, line) in lines.enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let r = serde_json::from_str(&line).map_err(|e| format!("line {}: {e}", n + 2))?;
+        records.push(r);
+    }
+    if records.len() != header.commands {
+        return Err(format!(
+            "truncated archive: {} of {} commands",
+            records.len(),
+            header.commands
+        ));
+    }
+    Ok(records)
+}
+
+// archives taken under different normalize rules may carry several rows
+// for one key, fold them the same way a merge into the table would.
+fn fold(records: Vec<Record>) -> Vec<Record> {
+    let mut map: HashMap<String, Record> = HashMap::new();
+    for r in records {
+        match map.get_mut(&r.normalized) {
+            Some(cur) => {
+                let cnt = cur.cnt + r.cnt;
+                let mut variants = cur.spellings();
+                variants.extend(r.spellings());
+                if r.when_run >= cur.when_run {
+                    *cur = r;
+                }
+                cur.cnt = cnt;
+                cur.variants = variants;
+            }
+            None => {
+                map.insert(r.normalized.clone(), r);
+            }
+        }
+    }
+    map.into_values().collect()
+}
+
+pub async fn backup(settings: &Settings) {
+    let records = match db::all_records().await {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("backup: {e}");
+            return;
+        }
+    };
+    match write_archive(&settings.archive, &records) {
+        Ok(()) => println!("{}: {} commands", settings.archive, records.len()),
+        Err(e) => eprintln!("{}: {e}", settings.archive),
+    }
+}
+
+pub async fn restore(settings: &Settings) {
+    let records = match read_archive(&settings.archive) {
+        Ok(r) => fold(r),
+        Err(e) => {
+            eprintln!("{}: {e}", settings.archive);
+            return;
+        }
+    };
+
+    if settings.merge {
+        let done = db::upsert_records(&records).await;
+        println!("{}: {done} commands merged", settings.archive);
+        return;
+    }
+    match db::replace_records(&records).await {
+        Ok(done) => println!("{}: {done} commands restored", settings.archive),
+        Err(e) => eprintln!("restore: {e}"),
+    }
+}
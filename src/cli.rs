@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(author, version)]
@@ -15,6 +15,12 @@ pub enum SubCommand {
         command: Vec<String>,
         #[arg(value_name = "EXIT_CODE", short, long)]
         exit: i32,
+        #[arg(short, long = "shell", value_enum, default_value_t = ShellKind::Bash)]
+        shell: ShellKind,
+        #[arg(value_name = "MS", short, long = "duration")]
+        duration: Option<i64>,
+        #[arg(value_name = "NAMES", short, long = "known")]
+        known: Option<String>,
     },
 
     Search {
@@ -23,14 +29,133 @@ pub enum SubCommand {
         bottom: bool,
         #[arg(short, long = "light")]
         light: bool,
+        #[arg(value_name = "FD", long = "fd")]
+        fd: Option<i32>,
     },
 
     Init {
+        #[arg(value_enum, default_value_t = ShellKind::Bash)]
+        shell: ShellKind,
         #[arg(short, long = "bottom")]
         bottom: bool,
         #[arg(short, long = "light")]
         light: bool,
+        #[arg(short, long = "sync")]
+        sync: bool,
     },
+
+    Import {
+        files: Vec<String>,
+        #[arg(short, long = "from", value_enum, default_value_t = ImportFormat::Bash)]
+        from: ImportFormat,
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    Export {
+        pattern: Vec<String>,
+        #[arg(short, long = "format", value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+        #[arg(short, long = "output")]
+        output: Option<String>,
+        #[arg(long = "since", value_parser = parse_time)]
+        since: Option<i64>,
+        #[arg(long = "until", value_parser = parse_time)]
+        until: Option<i64>,
+        #[arg(value_name = "EXIT_CODE", short, long = "exit")]
+        exit: Option<i32>,
+    },
+
+    Backup {
+        file: String,
+    },
+
+    Restore {
+        file: String,
+        #[arg(short, long = "merge")]
+        merge: bool,
+    },
+
+    Prune {
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    #[command(alias = "query")]
+    List {
+        pattern: Vec<String>,
+        #[arg(short, long = "limit")]
+        limit: Option<i64>,
+        #[arg(short, long = "offset", default_value_t = 0)]
+        offset: i64,
+        #[arg(short, long = "json")]
+        json: bool,
+        #[arg(short = '0', long = "null", conflicts_with = "json")]
+        null: bool,
+    },
+
+    Renormalize {
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    SyncHistfile {
+        #[arg(short, long = "limit")]
+        limit: Option<i64>,
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+        #[arg(value_name = "NAMES", short, long = "known")]
+        known: Option<String>,
+    },
+
+    // fed `history` by the bash integration to find deleted commands
+    #[command(hide = true)]
+    Forget {
+        digests: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ImportFormat {
+    Bash,
+    Zsh,
+    Fish,
+    Mcfly,
+    Atuin,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+    Bash,
+}
+
+// epoch seconds, a date (YYYY-MM-DD, local midnight), or a duration
+// such as `30d` meaning that long ago.
+fn parse_time(s: &str) -> Result<i64, String> {
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    if let Ok(secs) = s.parse::<i64>() {
+        return Ok(secs);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        return Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(|t| t.timestamp())
+            .ok_or_else(|| format!("invalid local time: {s}"));
+    }
+    let ago = humantime::parse_duration(s).map_err(|e| format!("invalid time '{s}': {e}"))?;
+    Ok(Local::now().timestamp() - ago.as_secs() as i64)
 }
 
 impl Cli {
@@ -1,9 +1,14 @@
-use serde::Deserialize;
-use std::sync::OnceLock;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashMap, sync::OnceLock, time::Duration};
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub database: DatabaseConfig,
+    pub encryption: Option<EncryptionConfig>,
+    pub retention: Option<RetentionConfig>,
+    pub shell: Option<ShellConfig>,
+    pub normalize: Option<NormalizeConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -16,6 +21,81 @@ pub struct DatabaseConfig {
     pub schema: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EncryptionConfig {
+    #[serde(default = "default_key_file")]
+    pub key_file: String,
+}
+
+fn default_key_file() -> String {
+    "~/.local/share/rhis/key".into()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionConfig {
+    pub max_rows: Option<i64>,
+    #[serde(default, deserialize_with = "duration")]
+    pub max_age: Option<Duration>,
+    pub keep_count: Option<i32>,
+    #[serde(default, deserialize_with = "duration")]
+    pub interval: Option<Duration>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShellConfig {
+    // extra commands that run their arguments, like `sudo` or `nohup`
+    #[serde(default)]
+    pub wrappers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NormalizeConfig {
+    // commands whose bundled short flags (`-la`) are split, in place of the built-in list
+    pub getopt: Option<Vec<String>>,
+    // compare text in Unicode NFC, so composed and decomposed spellings are one
+    #[serde(default)]
+    pub nfc: bool,
+    // read `$HOME/x` and the home directory as `~/x`, and `./x` as `x`
+    #[serde(default)]
+    pub paths: bool,
+    // keyed by command name, or by name and subcommands such as "git commit"
+    #[serde(default)]
+    pub commands: HashMap<String, CommandRule>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandRule {
+    // false keeps the flags in the order they were typed
+    pub sort: Option<bool>,
+    // leading positionals that name a subcommand, and select a more specific rule
+    pub subcommands: Option<usize>,
+    // options taking a value that may come before the subcommand, like git's `-C`
+    #[serde(default)]
+    pub options: Vec<String>,
+    // options left out of the key, together with their value
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    // arguments matching any of these are replaced with `*`
+    #[serde(default, deserialize_with = "patterns")]
+    pub mask: Vec<Regex>,
+}
+
+fn duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    let Some(s) = Option::<String>::deserialize(d)? else {
+        return Ok(None);
+    };
+    humantime::parse_duration(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn patterns<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|p| Regex::new(p).map_err(serde::de::Error::custom))
+        .collect()
+}
+
 static CONFIG: OnceLock<Config> = OnceLock::new();
 
 pub fn conf_init(path: &str) {
@@ -41,3 +121,8 @@ pub fn conf_init(path: &str) {
 pub fn conf_get() -> &'static Config {
     CONFIG.get().expect("config not initialized")
 }
+
+// for code that also runs before, or without, a config
+pub fn conf_try_get() -> Option<&'static Config> {
+    CONFIG.get()
+}
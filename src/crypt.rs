@@ -0,0 +1,165 @@
+use crate::conf;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    sync::OnceLock,
+};
+
+const NONCE_LEN: usize = 24;
+// keys the fingerprints in the journal of deleted commands, which every
+// setup keeps, encrypted or not
+const JOURNAL_KEY: &str = "~/.local/share/rhis/journal.key";
+
+struct Keys {
+    cipher: XChaCha20Poly1305,
+    mac: [u8; 32],
+}
+
+fn derive(master: &[u8], label: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master).unwrap();
+    mac.update(label);
+    mac.finalize().into_bytes().into()
+}
+
+fn read_key(path: &str) -> io::Result<Vec<u8>> {
+    STANDARD
+        .decode(fs::read_to_string(path)?.trim())
+        .ok()
+        .filter(|k| k.len() == 32)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid key file"))
+}
+
+// reads the key at `path`, or creates one there when there is none yet
+fn read_or_create(path: &str) -> io::Result<Vec<u8>> {
+    match read_key(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        read => return read,
+    }
+
+    let mut key = vec![0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    if let Some(dir) = Path::new(path).parent() {
+        _ = fs::create_dir_all(dir);
+    }
+    let mut f = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+    {
+        // another process got there first
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return read_key(path),
+        f => f?,
+    };
+    writeln!(f, "{}", STANDARD.encode(&key))?;
+    Ok(key)
+}
+
+fn key_path() -> Option<String> {
+    let c = conf::conf_get().encryption.as_ref()?;
+    Some(shellexpand::tilde(&c.key_file).into_owned())
+}
+
+// true when encryption is on but its key file is not there yet
+pub(crate) fn key_missing() -> bool {
+    key_path().is_some_and(|p| !Path::new(&p).exists())
+}
+
+// only for a table nothing has been sealed into yet: anywhere else the key
+// has to be copied over, or the rows could not be opened nor deduplicated
+pub(crate) fn create_key() -> io::Result<()> {
+    match key_path() {
+        Some(path) => read_or_create(&path).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+// None when the key file can neither be read nor created
+pub fn journal_key() -> Option<&'static [u8; 32]> {
+    static KEY: OnceLock<Option<[u8; 32]>> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let master = read_or_create(&shellexpand::tilde(JOURNAL_KEY)).ok()?;
+        Some(derive(&master, b"rhis-journal"))
+    })
+    .as_ref()
+}
+
+fn loaded() -> &'static Result<Option<Keys>, String> {
+    static KEYS: OnceLock<Result<Option<Keys>, String>> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let Some(path) = key_path() else {
+            return Ok(None);
+        };
+        let master = read_key(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                format!("key file '{path}' is missing, copy it from a machine that has it")
+            }
+            _ => format!("key file '{path}': {e}"),
+        })?;
+        Ok(Some(Keys {
+            cipher: XChaCha20Poly1305::new(&derive(&master, b"rhis-enc").into()),
+            mac: derive(&master, b"rhis-mac"),
+        }))
+    })
+}
+
+fn keys() -> Option<&'static Keys> {
+    loaded().as_ref().ok()?.as_ref()
+}
+
+// fails when encryption is on but its key can't be used; nothing may be
+// stored or looked up then, since it would go out in plain text
+pub fn ready() -> Result<(), &'static str> {
+    loaded().as_ref().map(|_| ()).map_err(String::as_str)
+}
+
+pub fn enabled() -> bool {
+    keys().is_some()
+}
+
+pub fn seal(plain: &str) -> String {
+    let Some(keys) = keys() else {
+        return plain.to_owned();
+    };
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let mut out = nonce.to_vec();
+    out.extend(
+        keys.cipher
+            .encrypt(XNonce::from_slice(&nonce), plain.as_bytes())
+            .unwrap(),
+    );
+    STANDARD.encode(out)
+}
+
+pub fn open(sealed: &str) -> Option<String> {
+    let Some(keys) = keys() else {
+        return Some(sealed.to_owned());
+    };
+    let bytes = STANDARD.decode(sealed).ok()?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ct) = bytes.split_at(NONCE_LEN);
+    let plain = keys.cipher.decrypt(XNonce::from_slice(nonce), ct).ok()?;
+    String::from_utf8(plain).ok()
+}
+
+pub fn digest(normalized: &str) -> String {
+    let Some(keys) = keys() else {
+        return normalized.to_owned();
+    };
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&keys.mac).unwrap();
+    mac.update(normalized.as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
@@ -1,9 +1,13 @@
+use crate::cli::ShellKind;
 use crate::conf;
+use crate::crypt;
 use crate::normalize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    postgres::{PgConnectOptions, PgPoolOptions},
-    PgPool, Row,
+    postgres::{PgConnectOptions, PgPoolOptions, PgRow},
+    PgConnection, PgPool, Row,
 };
+use std::collections::{HashMap, HashSet};
 use std::sync::OnceLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::task;
@@ -12,7 +16,7 @@ pub fn warmup() {
     pg_pool();
 }
 
-fn pg_pool() -> &'static PgPool {
+pub(crate) fn pg_pool() -> &'static PgPool {
     static POOL: OnceLock<Option<PgPool>> = OnceLock::new();
     let Some(pool) = POOL.get_or_init(|| {
         task::block_in_place(move || {
@@ -40,6 +44,20 @@ fn pg_pool() -> &'static PgPool {
                     eprintln!("    schema init failed: {e}");
                     return None;
                 }
+                if crypt::key_missing() {
+                    let sql = format!("SELECT NOT EXISTS (SELECT 1 FROM {}.commands)", c.schema);
+                    let fresh = sqlx::query_scalar(&sql).fetch_one(&pool).await;
+                    if matches!(fresh, Ok(true)) {
+                        if let Err(e) = crypt::create_key() {
+                            eprintln!("    key creation failed: {e}");
+                        }
+                    }
+                }
+                if crypt::enabled() {
+                    if let Err(e) = rekey_seeded(&pool, &c.schema).await {
+                        eprintln!("    spelling rekey failed: {e}");
+                    }
+                }
                 Some(pool)
             })
         })
@@ -49,7 +67,67 @@ fn pg_pool() -> &'static PgPool {
     pool
 }
 
-fn ignored(command: &str) -> bool {
+// init.sql seeds each command's spelling with its stored `original`, which
+// is the sealed text when encrypted, not the digest later adds look up; the
+// seeded spellings are moved over to their digest once, merging with the
+// spelling added since.
+async fn rekey_seeded(pool: &PgPool, schema: &str) -> Result<(), sqlx::Error> {
+    let flag = format!("SELECT value FROM {schema}.meta WHERE key = 'variants_seeded'");
+    if sqlx::query_scalar::<_, i64>(&flag).fetch_optional(pool).await? != Some(1) {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    let locked = sqlx::query_scalar::<_, i64>(&format!("{flag} FOR UPDATE"))
+        .fetch_optional(&mut *tx)
+        .await?;
+    if locked != Some(1) {
+        return Ok(());
+    }
+
+    let seeded: Vec<(i32, String)> = sqlx::query_as(&format!(
+        "SELECT command_id, original FROM {schema}.variants WHERE spelling = original"
+    ))
+    .fetch_all(&mut *tx)
+    .await?;
+    let (mut ids, mut old, mut spellings) = (vec![], vec![], vec![]);
+    for (id, original) in seeded {
+        let Some(text) = crypt::open(&original) else {
+            continue;
+        };
+        ids.push(id);
+        spellings.push(crypt::digest(&text));
+        old.push(original);
+    }
+    sqlx::query(&format!(
+        "INSERT INTO {schema}.variants (command_id, spelling, original, cnt, when_run) \
+         SELECT v.command_id, u.spelling, v.original, v.cnt, v.when_run \
+         FROM {schema}.variants v JOIN UNNEST($1::int[], $2::text[], $3::text[]) \
+             AS u(id, old, spelling) ON v.command_id = u.id AND v.spelling = u.old \
+         ON CONFLICT (command_id, spelling) DO UPDATE SET \
+             cnt = {schema}.variants.cnt + EXCLUDED.cnt, \
+             when_run = GREATEST({schema}.variants.when_run, EXCLUDED.when_run)"
+    ))
+    .bind(&ids)
+    .bind(&old)
+    .bind(&spellings)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(&format!(
+        "DELETE FROM {schema}.variants v USING UNNEST($1::int[], $2::text[]) AS u(id, old) \
+         WHERE v.command_id = u.id AND v.spelling = u.old"
+    ))
+    .bind(&ids)
+    .bind(&old)
+    .execute(&mut *tx)
+    .await?;
+    refresh_originals(&mut tx, &ids).await?;
+    sqlx::query(&format!("UPDATE {schema}.meta SET value = 2 WHERE key = 'variants_seeded'"))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+pub(crate) fn ignored(command: &str) -> bool {
     const IGNORED: [&str; 6] = ["pwd", "ls", "cd", "cd ..", "clear", "history"];
     command.is_empty()
         || command.starts_with(' ')
@@ -60,47 +138,166 @@ fn ignored(command: &str) -> bool {
 pub fn sanitize(raw: &str) -> String {
     raw.trim_end_matches(['\n', '\r', '\t', ' '])
         .chars()
-        .filter(|&c| c >= ' ' || c == '\t')
+        .filter(|&c| c >= ' ' || c == '\t' || c == '\n')
         .collect()
 }
 
-fn now_secs() -> i64 {
+pub(crate) fn now_secs() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs() as i64
 }
 
-pub async fn save_command(command: &str, exit_code: i32) {
+pub async fn save_command(
+    command: &str,
+    exit_code: i32,
+    shell: ShellKind,
+    duration_ms: Option<i64>,
+    known: &[String],
+) -> Saved {
     let command = sanitize(command);
     if ignored(&command) {
-        return;
+        return Saved::Ignored;
     }
-    if exit_code != 0 && !crate::shell::execute_able(&command, exit_code) {
-        return;
+    if exit_code != 0 && !crate::shell::execute_able(&command, exit_code, shell, known) {
+        return Saved::NotFound(command);
     }
 
-    let normalized = normalize::normalize(&command);
+    let normalized = crypt::digest(&normalize::normalize(&command));
     let when = now_secs();
     let pool = pg_pool();
     let schema = &conf::conf_get().database.schema;
 
     let sql = format!(
-        "INSERT INTO {schema}.commands (original, normalized, cnt, when_run, exit_code) \
-         VALUES ($1, $2, 1, $3, $4) \
+        "INSERT INTO {schema}.commands \
+             (original, normalized, cnt, when_run, exit_code, cwd, duration_ms) \
+         VALUES ($1, $2, 1, $3, $4, $5, $6) \
          ON CONFLICT (normalized) DO UPDATE SET \
-             original = EXCLUDED.original, \
              cnt = {schema}.commands.cnt + 1, \
              when_run = EXCLUDED.when_run, \
-             exit_code = EXCLUDED.exit_code"
+             exit_code = EXCLUDED.exit_code, \
+             cwd = EXCLUDED.cwd, \
+             duration_ms = EXCLUDED.duration_ms \
+         RETURNING id"
     );
-    _ = sqlx::query(&sql)
-        .bind(command)
-        .bind(&normalized)
-        .bind(when)
-        .bind(exit_code)
-        .execute(pool)
-        .await;
+    let variant = format!(
+        "INSERT INTO {schema}.variants (command_id, spelling, original, cnt, when_run) \
+         VALUES ($1, $2, $3, 1, $4) \
+         ON CONFLICT (command_id, spelling) DO UPDATE SET \
+             original = EXCLUDED.original, \
+             cnt = {schema}.variants.cnt + 1, \
+             when_run = EXCLUDED.when_run"
+    );
+    let cwd = std::env::current_dir()
+        .ok()
+        .map(|d| crypt::seal(&d.to_string_lossy()));
+    let sealed = crypt::seal(&command);
+
+    let _: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        let id: i32 = sqlx::query_scalar(&sql)
+            .bind(&sealed)
+            .bind(&normalized)
+            .bind(when)
+            .bind(exit_code)
+            .bind(cwd)
+            .bind(duration_ms)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query(&variant)
+            .bind(id)
+            .bind(crypt::digest(&command))
+            .bind(&sealed)
+            .bind(when)
+            .execute(&mut *tx)
+            .await?;
+        refresh_originals(&mut tx, &[id]).await?;
+        tx.commit().await
+    }
+    .await;
+    Saved::Stored
+}
+
+// points `commands.original` at the most used spelling, the latest on a tie
+pub(crate) async fn refresh_originals(
+    conn: &mut PgConnection,
+    ids: &[i32],
+) -> Result<(), sqlx::Error> {
+    let schema = &conf::conf_get().database.schema;
+    sqlx::query(&format!(
+        "UPDATE {schema}.commands c SET original = v.original FROM \
+             (SELECT DISTINCT ON (command_id) command_id, original FROM {schema}.variants \
+              WHERE command_id = ANY($1) ORDER BY command_id, cnt DESC, when_run DESC) v \
+         WHERE c.id = v.command_id"
+    ))
+    .bind(ids)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// the other spellings of the command `original` is stored under, most used first
+pub async fn variants(original: &str, pattern: &str) -> Vec<Match> {
+    let schema = &conf::conf_get().database.schema;
+    let sql = format!(
+        "SELECT v.original, v.when_run, v.cnt, c.exit_code FROM {schema}.variants v \
+         JOIN {schema}.commands c ON c.id = v.command_id \
+         WHERE c.normalized = $1 ORDER BY v.cnt DESC, v.when_run DESC"
+    );
+    let rows = match sqlx::query(&sql)
+        .bind(crypt::digest(&normalize::normalize(original)))
+        .fetch_all(pg_pool())
+        .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("variants: {e}");
+            return vec![];
+        }
+    };
+
+    rows.iter()
+        .filter_map(|row| Some((crypt::open(row.get(0))?, row)))
+        .filter(|(text, _)| text != original)
+        .map(|(text, row)| Match {
+            variant: true,
+            ..to_match(text, row, pattern)
+        })
+        .collect()
+}
+
+// the most used commands, decrypted, for fuzzy lookups done client side
+pub async fn frequent_commands(limit: i64) -> Vec<(String, i32)> {
+    let pool = pg_pool();
+    let schema = &conf::conf_get().database.schema;
+    let sql = format!(
+        "SELECT original, cnt FROM {schema}.commands \
+         ORDER BY cnt DESC, when_run DESC LIMIT $1"
+    );
+    let rows = sqlx::query(&sql)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+    rows.iter()
+        .filter_map(|row| Some((crypt::open(row.get(0))?, row.get(1))))
+        .collect()
+}
+
+// rows match on their normalized form, so the highlight is located there
+// and mapped back onto the original spelling.
+fn to_match(original: String, row: &PgRow, pattern: &str) -> Match {
+    let bounds =
+        normalize::normalize_mapped(&original).bounds(&original, &normalize::normalize(pattern));
+    Match {
+        cmd: original,
+        last_run: row.get(1),
+        cnt: row.get(2),
+        exit_code: row.get(3),
+        match_bounds: bounds,
+        variant: false,
+    }
 }
 
 pub async fn find_matches(
@@ -108,6 +305,10 @@ pub async fn find_matches(
     limit: i64,
     offset: i64,
 ) -> (Vec<Match>, i64) {
+    if crypt::enabled() {
+        return find_sealed_matches(pattern, limit, offset).await;
+    }
+
     let pool = pg_pool();
     let schema = &conf::conf_get().database.schema;
     let normalized_pattern = normalize::normalize(pattern);
@@ -118,7 +319,7 @@ pub async fn find_matches(
     };
 
     let sql = format!(
-        "SELECT original, when_run FROM {schema}.commands \
+        "SELECT original, when_run, cnt, exit_code FROM {schema}.commands \
          WHERE normalized LIKE $1 \
          ORDER BY when_run DESC LIMIT $2 OFFSET $3"
     );
@@ -138,19 +339,7 @@ pub async fn find_matches(
 
     let commands: Vec<Match> = rows
         .iter()
-        .map(|row| {
-            let original: String = row.get(0);
-            let when_run: i64 = row.get(1);
-            let bounds = original
-                .match_indices(pattern)
-                .map(|(i, _)| (i, i + pattern.len()))
-                .collect();
-            Match {
-                cmd: original,
-                last_run: when_run,
-                match_bounds: bounds,
-            }
-        })
+        .map(|row| to_match(row.get(0), row, pattern))
         .collect();
 
     let count_sql = format!(
@@ -166,8 +355,295 @@ pub async fn find_matches(
     (commands, total)
 }
 
+// the server only sees ciphertext, so rows are scanned newest first in
+// pages and filtered here; total is not known up front, it is reported as
+// one past what we have whenever unscanned rows remain.
+async fn find_sealed_matches(
+    pattern: &str,
+    limit: i64,
+    offset: i64,
+) -> (Vec<Match>, i64) {
+    const SCAN_PAGE: i64 = 500;
+    let pool = pg_pool();
+    let schema = &conf::conf_get().database.schema;
+    let mut needle = normalize::normalize(pattern);
+    if pattern.ends_with(' ') || pattern.ends_with('\t') {
+        needle.push(' ');
+    }
+
+    let sql = format!(
+        "SELECT original, when_run, cnt, exit_code FROM {schema}.commands \
+         ORDER BY when_run DESC LIMIT $1 OFFSET $2"
+    );
+    let mut commands = vec![];
+    let mut skipped = 0;
+    let mut scanned = 0;
+    loop {
+        let rows = match sqlx::query(&sql)
+            .bind(SCAN_PAGE)
+            .bind(scanned)
+            .fetch_all(pool)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("find_matches: {e}");
+                return (vec![], 0);
+            }
+        };
+        if rows.is_empty() {
+            let total = offset + commands.len() as i64;
+            return (commands, total);
+        }
+        scanned += rows.len() as i64;
+
+        for row in rows {
+            let Some(original) = crypt::open(row.get(0)) else {
+                continue;
+            };
+            if !normalize::normalize(&original).contains(&needle) {
+                continue;
+            }
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            if commands.len() as i64 == limit {
+                return (commands, offset + limit + 1);
+            }
+            commands.push(to_match(original, &row, pattern));
+        }
+    }
+}
+
+// merges pre-aggregated rows: counts add up, the newest run wins.
+pub async fn upsert_records(records: &[Record]) -> u64 {
+    let mut conn = match pg_pool().acquire().await {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("upsert_records: {e}");
+            return 0;
+        }
+    };
+    upsert_with(&mut conn, records).await.unwrap_or_else(|(done, e)| {
+        eprintln!("upsert_records: {e}");
+        done
+    })
+}
+
+async fn upsert_with(conn: &mut PgConnection, records: &[Record]) -> Result<u64, (u64, sqlx::Error)> {
+    let schema = &conf::conf_get().database.schema;
+    let newer = format!("EXCLUDED.when_run >= {schema}.commands.when_run");
+    let sql = format!(
+        "INSERT INTO {schema}.commands \
+             (original, normalized, cnt, when_run, exit_code, cwd, duration_ms) \
+         SELECT * FROM UNNEST($1::text[], $2::text[], $3::int[], $4::bigint[], $5::int[], \
+             $6::text[], $7::bigint[]) \
+         ON CONFLICT (normalized) DO UPDATE SET \
+             original = CASE WHEN {newer} THEN EXCLUDED.original ELSE {schema}.commands.original END, \
+             exit_code = CASE WHEN {newer} THEN EXCLUDED.exit_code ELSE {schema}.commands.exit_code END, \
+             cwd = CASE WHEN {newer} THEN EXCLUDED.cwd ELSE {schema}.commands.cwd END, \
+             duration_ms = CASE WHEN {newer} \
+                 THEN EXCLUDED.duration_ms ELSE {schema}.commands.duration_ms END, \
+             cnt = {schema}.commands.cnt + EXCLUDED.cnt, \
+             when_run = GREATEST({schema}.commands.when_run, EXCLUDED.when_run) \
+         RETURNING id"
+    );
+    let variants = format!(
+        "INSERT INTO {schema}.variants (command_id, spelling, original, cnt, when_run) \
+         SELECT c.id, u.spelling, MIN(u.original), SUM(u.cnt), MAX(u.when_run) \
+         FROM UNNEST($1::text[], $2::text[], $3::text[], $4::int[], $5::bigint[]) \
+             AS u(normalized, spelling, original, cnt, when_run) \
+         JOIN {schema}.commands c ON c.normalized = u.normalized \
+         GROUP BY c.id, u.spelling \
+         ON CONFLICT (command_id, spelling) DO UPDATE SET \
+             cnt = {schema}.variants.cnt + EXCLUDED.cnt, \
+             when_run = GREATEST({schema}.variants.when_run, EXCLUDED.when_run)"
+    );
+
+    let mut done = 0;
+    for chunk in records.chunks(1000) {
+        let originals: Vec<String> = chunk.iter().map(|r| crypt::seal(&r.original)).collect();
+        let normalized: Vec<String> = chunk.iter().map(|r| crypt::digest(&r.normalized)).collect();
+        let cnts: Vec<i32> = chunk.iter().map(|r| r.cnt).collect();
+        let whens: Vec<i64> = chunk.iter().map(|r| r.when_run).collect();
+        let exit_codes: Vec<i32> = chunk.iter().map(|r| r.exit_code).collect();
+        let cwds: Vec<Option<String>> = chunk.iter().map(|r| r.cwd.as_deref().map(crypt::seal)).collect();
+        let durations: Vec<Option<i64>> = chunk.iter().map(|r| r.duration_ms).collect();
+        let mut spelled_as = (vec![], vec![], vec![], vec![], vec![]);
+        for (r, key) in chunk.iter().zip(&normalized) {
+            for v in r.spellings() {
+                spelled_as.0.push(key.clone());
+                spelled_as.1.push(crypt::digest(&v.original));
+                spelled_as.2.push(crypt::seal(&v.original));
+                spelled_as.3.push(v.cnt);
+                spelled_as.4.push(v.when_run);
+            }
+        }
+        let ids: Vec<i32> = match sqlx::query_scalar(&sql)
+            .bind(&originals)
+            .bind(&normalized)
+            .bind(&cnts)
+            .bind(&whens)
+            .bind(exit_codes)
+            .bind(cwds)
+            .bind(durations)
+            .fetch_all(&mut *conn)
+            .await
+        {
+            Ok(ids) => ids,
+            Err(e) => return Err((done, e)),
+        };
+        let spelled = sqlx::query(&variants)
+            .bind(spelled_as.0)
+            .bind(spelled_as.1)
+            .bind(spelled_as.2)
+            .bind(spelled_as.3)
+            .bind(spelled_as.4)
+            .execute(&mut *conn)
+            .await;
+        if let Err(e) = spelled {
+            return Err((done, e));
+        }
+        if let Err(e) = refresh_originals(conn, &ids).await {
+            return Err((done, e));
+        }
+        done += ids.len() as u64;
+    }
+    Ok(done)
+}
+
+// swaps the whole table for `records` in one transaction
+pub async fn replace_records(records: &[Record]) -> Result<u64, sqlx::Error> {
+    let schema = &conf::conf_get().database.schema;
+    let mut tx = pg_pool().begin().await?;
+    sqlx::query(&format!("DELETE FROM {schema}.commands"))
+        .execute(&mut *tx)
+        .await?;
+    let done = upsert_with(&mut tx, records).await.map_err(|(_, e)| e)?;
+    tx.commit().await?;
+    Ok(done)
+}
+
+// rows come back decrypted; under encryption `normalized` only holds a
+// digest, so it is recomputed from the original.
+fn to_records(rows: &[PgRow]) -> Vec<(i32, Record)> {
+    let sealed = crypt::enabled();
+    rows.iter()
+        .filter_map(|row| {
+            let original = crypt::open(row.get(1))?;
+            let normalized = if sealed {
+                normalize::normalize(&original)
+            } else {
+                row.get(2)
+            };
+            let cwd: Option<String> = row.get(6);
+            let record = Record {
+                original,
+                normalized,
+                cnt: row.get(3),
+                when_run: row.get(4),
+                exit_code: row.get(5),
+                cwd: cwd.as_deref().and_then(crypt::open),
+                duration_ms: row.get(7),
+                variants: vec![],
+            };
+            Some((row.get(0), record))
+        })
+        .collect()
+}
+
+pub async fn load_records(after: i32, limit: i64) -> Result<Vec<(i32, Record)>, sqlx::Error> {
+    let pool = pg_pool();
+    let schema = &conf::conf_get().database.schema;
+    let sql = format!(
+        "SELECT id, original, normalized, cnt, when_run, exit_code, cwd, duration_ms \
+         FROM {schema}.commands WHERE id > $1 ORDER BY id LIMIT $2"
+    );
+    let rows = sqlx::query(&sql)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+    with_variants(to_records(&rows)).await
+}
+
+// fills in every spelling of each command, most used first
+async fn with_variants(mut records: Vec<(i32, Record)>) -> Result<Vec<(i32, Record)>, sqlx::Error> {
+    let schema = &conf::conf_get().database.schema;
+    let ids: Vec<i32> = records.iter().map(|(id, _)| *id).collect();
+    let rows = sqlx::query(&format!(
+        "SELECT command_id, original, cnt, when_run FROM {schema}.variants \
+         WHERE command_id = ANY($1) ORDER BY cnt DESC, when_run DESC"
+    ))
+    .bind(ids)
+    .fetch_all(pg_pool())
+    .await?;
+
+    let mut by_id: HashMap<i32, Vec<Variant>> = HashMap::new();
+    for row in &rows {
+        let Some(original) = crypt::open(row.get(1)) else {
+            continue;
+        };
+        by_id.entry(row.get(0)).or_default().push(Variant {
+            original,
+            cnt: row.get(2),
+            when_run: row.get(3),
+        });
+    }
+    for (id, r) in &mut records {
+        r.variants = by_id.remove(id).unwrap_or_default();
+    }
+    Ok(records)
+}
+
+// the `limit` most recently run commands, newest first
+pub async fn recent_records(limit: i64) -> Result<Vec<Record>, sqlx::Error> {
+    let pool = pg_pool();
+    let schema = &conf::conf_get().database.schema;
+    let sql = format!(
+        "SELECT id, original, normalized, cnt, when_run, exit_code, cwd, duration_ms \
+         FROM {schema}.commands ORDER BY when_run DESC LIMIT $1"
+    );
+    let rows = sqlx::query(&sql).bind(limit).fetch_all(pool).await?;
+    Ok(to_records(&rows).into_iter().map(|(_, r)| r).collect())
+}
+
+// which of the normalized `keys` the database holds a command for
+pub async fn stored_keys(keys: &[String]) -> Result<HashSet<String>, sqlx::Error> {
+    let pool = pg_pool();
+    let schema = &conf::conf_get().database.schema;
+    let mut by_digest: HashMap<String, &String> = HashMap::new();
+    for key in keys {
+        by_digest.insert(crypt::digest(key), key);
+    }
+    let digests: Vec<&str> = by_digest.keys().map(String::as_str).collect();
+    let found: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT normalized FROM {schema}.commands WHERE normalized = ANY($1)"
+    ))
+    .bind(digests)
+    .fetch_all(pool)
+    .await?;
+    Ok(found
+        .iter()
+        .filter_map(|d| by_digest.get(d).map(|k| k.to_string()))
+        .collect())
+}
+
+pub async fn all_records() -> Result<Vec<Record>, sqlx::Error> {
+    let mut records = vec![];
+    let mut after = 0;
+    loop {
+        let batch = load_records(after, 1000).await?;
+        let Some(&(last, _)) = batch.last() else { break };
+        after = last;
+        records.extend(batch.into_iter().map(|(_, r)| r));
+    }
+    Ok(records)
+}
+
 pub async fn delete_command(original: &str) {
-    let normalized = normalize::normalize(original);
+    let normalized = crypt::digest(&normalize::normalize(original));
     let pool = pg_pool();
     let schema = &conf::conf_get().database.schema;
     let sql = format!("DELETE FROM {schema}.commands WHERE normalized = $1");
@@ -177,9 +653,56 @@ pub async fn delete_command(original: &str) {
         .await;
 }
 
-#[derive(Debug, Clone)]
+pub enum Saved {
+    Stored,
+    Ignored,
+    NotFound(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Match {
+    #[serde(rename = "command")]
     pub cmd: String,
     pub last_run: i64,
+    pub cnt: i32,
+    pub exit_code: i32,
     pub match_bounds: Vec<(usize, usize)>,
+    // another spelling listed under the command above it
+    #[serde(skip)]
+    pub variant: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub original: String,
+    pub normalized: String,
+    pub cnt: i32,
+    pub when_run: i64,
+    pub exit_code: i32,
+    pub cwd: Option<String>,
+    pub duration_ms: Option<i64>,
+    // every spelling typed for the command; empty when only `original` is known
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub original: String,
+    pub cnt: i32,
+    pub when_run: i64,
+}
+
+impl Record {
+    // what to store as its spellings; without a list, `original` stands for all runs
+    pub fn spellings(&self) -> Vec<Variant> {
+        if !self.variants.is_empty() {
+            return self.variants.clone();
+        }
+        vec![Variant {
+            original: self.original.clone(),
+            cnt: self.cnt,
+            when_run: self.when_run,
+        }]
+    }
 }
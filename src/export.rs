@@ -0,0 +1,97 @@
+use crate::{
+    cli::ExportFormat,
+    db::{self, Record},
+    normalize,
+    settings::Settings,
+};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, stdout, BufWriter, Write},
+};
+
+#[derive(Serialize)]
+struct Line<'a> {
+    id: i32,
+    #[serde(flatten)]
+    record: &'a Record,
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn opt<T: ToString>(v: &Option<T>) -> String {
+    v.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+fn write_records<W: Write>(out: &mut W, format: ExportFormat, rows: &[(i32, Record)]) -> io::Result<()> {
+    if let ExportFormat::Csv = format {
+        writeln!(out, "id,original,normalized,cnt,when_run,exit_code,cwd,duration_ms")?;
+    }
+
+    for (id, r) in rows {
+        match format {
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut *out, &Line { id: *id, record: r })?;
+                writeln!(out)?;
+            }
+            ExportFormat::Csv => writeln!(
+                out,
+                "{id},{},{},{},{},{},{},{}",
+                csv_field(&r.original),
+                csv_field(&r.normalized),
+                r.cnt,
+                r.when_run,
+                r.exit_code,
+                csv_field(&opt(&r.cwd)),
+                opt(&r.duration_ms),
+            )?,
+            ExportFormat::Bash => writeln!(out, "#{}\n{}", r.when_run, r.original)?,
+        }
+    }
+    out.flush()
+}
+
+pub async fn run(settings: &Settings) {
+    let mut needle = normalize::normalize(&settings.command);
+    if settings.command.ends_with(' ') {
+        needle.push(' ');
+    }
+
+    let keep = |r: &Record| {
+        settings.since.is_none_or(|t| r.when_run >= t)
+            && settings.until.is_none_or(|t| r.when_run < t)
+            && settings.exit_filter.is_none_or(|c| r.exit_code == c)
+            && r.normalized.contains(&needle)
+    };
+
+    let mut rows = vec![];
+    let mut after = 0;
+    loop {
+        let batch = match db::load_records(after, 1000).await {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("export: {e}");
+                return;
+            }
+        };
+        let Some(&(last, _)) = batch.last() else { break };
+        after = last;
+        rows.extend(batch.into_iter().filter(|(_, r)| keep(r)));
+    }
+    rows.sort_by_key(|(id, r)| (r.when_run, *id));
+
+    let result = match &settings.output {
+        Some(path) => File::create(path)
+            .and_then(|f| write_records(&mut BufWriter::new(f), settings.export_format, &rows)),
+        None => write_records(&mut BufWriter::new(stdout().lock()), settings.export_format, &rows),
+    };
+    if let Err(e) = result {
+        eprintln!("export: {e}");
+    }
+}
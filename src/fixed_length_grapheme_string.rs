@@ -48,15 +48,13 @@ impl FixedLengthGraphemeString {
         }
 
         if self.len > self.max_grapheme_length {
-            let mut len = 0;
             let mut str = String::new();
             mem::swap(&mut str, &mut self.string);
 
-            for grapheme in str.graphemes(true) {
-                if len + 3 >= self.max_grapheme_length {
+            for (len, grapheme) in str.graphemes(true).enumerate() {
+                if len + 3 >= self.max_grapheme_length as usize {
                     break;
                 }
-                len += 1;
                 self.string.push_str(grapheme);
             }
             self.string.push_str("...");
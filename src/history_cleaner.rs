@@ -1,6 +1,183 @@
-use crate::{db, shell_history};
+use crate::{crypt, db, settings::Settings, shell_history};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+// one fingerprint per deleted command, appended by `clean` and tailed by
+// every bash session so it can drop the command from its in-memory history.
+// Its first line names a generation, which changes whenever it is compacted.
+const JOURNAL: &str = "~/.local/share/rhis/deleted";
+// compacting down to this many leaves sessions that are behind room to catch up
+const JOURNAL_KEEP: usize = 500;
+
+fn fingerprint_with(key: &[u8], command: &str) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(db::sanitize(command).trim_start().as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+// the journal must not leak what was deleted, so only a keyed hash of the
+// text as it would be stored is kept; guessing it takes the user's key.
+pub(crate) fn fingerprint(command: &str) -> Option<String> {
+    Some(fingerprint_with(crypt::journal_key()?, command))
+}
+
+fn generation() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("#{nanos}")
+}
+
+// a journal from before generations were kept holds plain hashes nothing
+// matches any more, so it starts over as well.
+fn journaled(contents: &[u8], digests: &[String], fresh: &str) -> Vec<u8> {
+    let text = String::from_utf8_lossy(contents);
+    let mut lines = text.lines();
+    let (mut header, mut kept): (&str, Vec<&str>) = match lines.next() {
+        Some(h) if h.starts_with('#') => (h, lines.collect()),
+        _ => (fresh, vec![]),
+    };
+    kept.extend(digests.iter().map(String::as_str));
+    if kept.len() > 2 * JOURNAL_KEEP {
+        kept.drain(..kept.len() - JOURNAL_KEEP);
+        header = fresh;
+    }
+
+    let mut out = format!("{header}\n");
+    for line in kept {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+fn journal(commands: &[&str]) -> io::Result<()> {
+    let Some(key) = crypt::journal_key() else {
+        return Ok(());
+    };
+    let digests: Vec<String> = commands.iter().map(|c| fingerprint_with(key, c)).collect();
+    let path = PathBuf::from(shellexpand::tilde(JOURNAL).as_ref());
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)?;
+    let fresh = generation();
+    shell_history::rewrite(&path, |contents| {
+        Some(journaled(contents, &digests, &fresh))
+    })
+}
+
+pub(crate) fn forgotten() -> HashSet<String> {
+    fs::read_to_string(shellexpand::tilde(JOURNAL).as_ref())
+        .map(|j| j.lines().skip(1).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+// every spelling of the command goes, not only the one on screen: the rest
+// would otherwise stay in HISTFILE and come back with the next sync.
 pub fn clean(original: &str) {
-    crate::runtime().block_on(db::delete_command(original));
-    shell_history::delete_lines(original);
+    let others = crate::runtime().block_on(async {
+        let others = db::variants(original, "").await;
+        db::delete_command(original).await;
+        others
+    });
+    let mut spellings = vec![original];
+    spellings.extend(others.iter().map(|m| m.cmd.as_str()));
+    shell_history::delete_lines(&spellings);
+    _ = journal(&spellings);
+}
+
+// `history` output numbers entries consecutively; a line that does not carry
+// the next number continues a multi-line entry.
+fn entries(listing: &str) -> Vec<(usize, String)> {
+    let mut entries: Vec<(usize, String)> = vec![];
+    for line in listing.lines() {
+        let rest = line.trim_start_matches(' ');
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        // bash prints the number, then `*` for a modified entry or a space
+        let numbered = rest[..digits].parse::<usize>().ok().and_then(|n| {
+            let text = rest[digits..].strip_prefix(['*', ' '])?;
+            Some((n, text.strip_prefix(' ')?))
+        });
+        match (numbered, entries.last_mut()) {
+            (Some((n, text)), last) if last.as_ref().is_none_or(|(prev, _)| n == prev + 1) => {
+                entries.push((n, text.to_string()))
+            }
+            (_, Some((_, text))) => {
+                text.push('\n');
+                text.push_str(line);
+            }
+            (_, None) => {}
+        }
+    }
+    entries
+}
+
+// reads `history` output on stdin and prints, last first, the numbers of
+// the entries whose fingerprint is listed, ready for `history -d`.
+pub fn forget(settings: &Settings) {
+    let mut listing = String::new();
+    if io::stdin().read_to_string(&mut listing).is_err() {
+        return;
+    }
+    let Some(key) = crypt::journal_key() else {
+        return;
+    };
+    let mut out = io::stdout().lock();
+    for (n, text) in entries(&listing).iter().rev() {
+        if settings.digests.contains(&fingerprint_with(key, text)) && writeln!(out, "{n}").is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_listing() {
+        let listing = "  9  ls\n   10* cat <<EOF\n   5  x\nEOF\n   11  echo $SECRET\n";
+        let entries = entries(listing);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], (10, "cat <<EOF\n   5  x\nEOF".to_string()));
+        assert_eq!(entries[2], (11, "echo $SECRET".to_string()));
+        assert_eq!(
+            fingerprint_with(b"key", &entries[2].1),
+            fingerprint_with(b"key", "echo $SECRET\n")
+        );
+        assert_ne!(
+            fingerprint_with(b"key", "echo $SECRET"),
+            fingerprint_with(b"other", "echo $SECRET")
+        );
+    }
+
+    #[test]
+    fn journal_compaction() {
+        let digests = |n: usize| (0..n).map(|i| format!("{i:064x}")).collect::<Vec<_>>();
+        // a journal of unkeyed hashes from before is dropped
+        let out = journaled(b"abc\ndef\n", &digests(1), "#2");
+        assert_eq!(String::from_utf8(out).unwrap(), format!("#2\n{:064x}\n", 0));
+
+        let full = journaled(b"#1\n", &digests(2 * JOURNAL_KEEP), "#2");
+        assert!(full.starts_with(b"#1\n"));
+        let text = String::from_utf8(journaled(&full, &digests(1), "#3")).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "#3");
+        assert_eq!(lines.len(), JOURNAL_KEEP + 1);
+        assert_eq!(lines[JOURNAL_KEEP], format!("{:064x}", 0));
+    }
 }
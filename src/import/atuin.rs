@@ -0,0 +1,31 @@
+use super::Item;
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Row, SqliteConnection};
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const NANOS_PER_MS: i64 = 1_000_000;
+
+pub async fn load(path: &str) -> Result<Vec<Item>, sqlx::Error> {
+    let opt = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&opt).await?;
+    let rows = sqlx::query(
+        "SELECT command, timestamp, duration, exit, cwd FROM history \
+         WHERE deleted_at IS NULL ORDER BY timestamp",
+    )
+    .fetch_all(&mut conn)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            // atuin keeps nanoseconds and uses -1 for "unknown"
+            let duration: i64 = row.get(2);
+            let exit_code: i64 = row.get(3);
+            Item {
+                command: row.get(0),
+                when: Some(row.get::<i64, _>(1) / NANOS_PER_SEC),
+                exit_code: if exit_code < 0 { 0 } else { exit_code as i32 },
+                cwd: row.get::<Option<String>, _>(4).filter(|d| d != "unknown"),
+                duration_ms: (duration >= 0).then_some(duration / NANOS_PER_MS),
+            }
+        })
+        .collect())
+}
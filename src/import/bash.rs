@@ -0,0 +1,13 @@
+use super::Item;
+use crate::shell_history;
+
+pub fn parse(contents: &[u8]) -> Vec<Item> {
+    shell_history::parse_entries(&String::from_utf8_lossy(contents))
+        .into_iter()
+        .map(|e| Item {
+            command: e.command,
+            when: e.when,
+            ..Default::default()
+        })
+        .collect()
+}
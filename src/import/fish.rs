@@ -0,0 +1,56 @@
+use super::Item;
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// fish_history is a yaml-like list of `- cmd:` records, each optionally
+// followed by an indented `when:` and a `paths:` list.
+pub fn parse(contents: &[u8]) -> Vec<Item> {
+    let text = String::from_utf8_lossy(contents);
+    let mut items: Vec<Item> = vec![];
+    for line in text.lines() {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            items.push(Item {
+                command: unescape(cmd),
+                ..Default::default()
+            });
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some(last) = items.last_mut() {
+                last.when = when.trim().parse().ok();
+            }
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records() {
+        let items = parse(b"- cmd: cd /tmp\n  when: 1700000000\n  paths:\n    - /tmp\n- cmd: echo a\\\\nb\\nc\n  when: 1700000001\n");
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].command, "cd /tmp");
+        assert_eq!(items[0].when, Some(1700000000));
+        assert_eq!(items[1].command, "echo a\\nb\nc");
+    }
+}
@@ -0,0 +1,20 @@
+use super::Item;
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Row, SqliteConnection};
+
+pub async fn load(path: &str) -> Result<Vec<Item>, sqlx::Error> {
+    let opt = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&opt).await?;
+    let rows = sqlx::query("SELECT cmd, when_run, exit_code, dir FROM commands ORDER BY id")
+        .fetch_all(&mut conn)
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| Item {
+            command: row.get(0),
+            when: row.get(1),
+            exit_code: row.get::<Option<i32>, _>(2).unwrap_or(0),
+            cwd: row.get(3),
+            duration_ms: None,
+        })
+        .collect())
+}
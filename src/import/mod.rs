@@ -0,0 +1,141 @@
+mod atuin;
+mod bash;
+mod fish;
+mod mcfly;
+mod zsh;
+
+use crate::{
+    cli::ImportFormat,
+    db::{self, Record},
+    normalize,
+    settings::Settings,
+    shell_history,
+};
+use std::{collections::HashMap, fs, path::Path, time::UNIX_EPOCH};
+
+#[derive(Debug, Default)]
+pub struct Item {
+    pub command: String,
+    pub when: Option<i64>,
+    pub exit_code: i32,
+    pub cwd: Option<String>,
+    pub duration_ms: Option<i64>,
+}
+
+pub(crate) fn modified_secs(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_else(db::now_secs)
+}
+
+// folds repeated commands into one row per normalized form, the way
+// `save_command` would have if it had seen them one by one.
+pub(crate) fn collapse(items: Vec<Item>, mtime: i64) -> Vec<Record> {
+    // undated entries keep their order, one second apart, ending at mtime
+    let n = items.len() as i64;
+    let mut map: HashMap<String, Record> = HashMap::new();
+    for (i, item) in items.into_iter().enumerate() {
+        let command = db::sanitize(&item.command);
+        if db::ignored(&command) {
+            continue;
+        }
+        let when_run = item.when.unwrap_or(mtime - n + i as i64 + 1);
+        let normalized = normalize::normalize(&command);
+        match map.get_mut(&normalized) {
+            Some(r) => {
+                r.cnt += 1;
+                if when_run >= r.when_run {
+                    r.original = command;
+                    r.when_run = when_run;
+                    r.exit_code = item.exit_code;
+                    r.cwd = item.cwd;
+                    r.duration_ms = item.duration_ms;
+                }
+            }
+            None => {
+                map.insert(
+                    normalized.clone(),
+                    Record {
+                        original: command,
+                        normalized,
+                        cnt: 1,
+                        when_run,
+                        exit_code: item.exit_code,
+                        cwd: item.cwd,
+                        duration_ms: item.duration_ms,
+                        variants: vec![],
+                    },
+                );
+            }
+        }
+    }
+    map.into_values().collect()
+}
+
+fn default_path(format: ImportFormat) -> Option<String> {
+    let path = match format {
+        ImportFormat::Bash => {
+            return shell_history::history_file_path().map(|p| p.to_string_lossy().into_owned())
+        }
+        ImportFormat::Zsh => match std::env::var("HISTFILE") {
+            Ok(p) if !p.is_empty() => return Some(p),
+            _ => "~/.zsh_history",
+        },
+        ImportFormat::Fish => "~/.local/share/fish/fish_history",
+        ImportFormat::Mcfly => "~/.local/share/mcfly/history.db",
+        ImportFormat::Atuin => "~/.local/share/atuin/history.db",
+    };
+    Some(shellexpand::tilde(path).into_owned())
+}
+
+async fn load(format: ImportFormat, path: &str) -> Result<Vec<Item>, String> {
+    let text_format = |parse: fn(&[u8]) -> Vec<Item>| {
+        fs::read(path).map(|c| parse(&c)).map_err(|e| e.to_string())
+    };
+    match format {
+        ImportFormat::Bash => text_format(bash::parse),
+        ImportFormat::Zsh => text_format(zsh::parse),
+        ImportFormat::Fish => text_format(fish::parse),
+        ImportFormat::Mcfly => mcfly::load(path).await.map_err(|e| e.to_string()),
+        ImportFormat::Atuin => atuin::load(path).await.map_err(|e| e.to_string()),
+    }
+}
+
+async fn import_file(format: ImportFormat, path: &str, dry_run: bool) {
+    let items = match load(format, path).await {
+        Ok(items) => items,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return;
+        }
+    };
+    let n = items.len();
+    let records = collapse(items, modified_secs(Path::new(path)));
+    if dry_run {
+        println!("{path}: {n} entries, {} distinct commands (dry run)", records.len());
+        return;
+    }
+    let done = db::upsert_records(&records).await;
+    println!("{path}: {n} entries, {} distinct commands, {done} imported", records.len());
+}
+
+pub async fn run(settings: &Settings) {
+    let files = if settings.files.is_empty() {
+        match default_path(settings.import_from) {
+            Some(p) => vec![p],
+            None => {
+                eprintln!("no file given and HISTFILE is not set");
+                return;
+            }
+        }
+    } else {
+        settings.files.clone()
+    };
+
+    for path in &files {
+        import_file(settings.import_from, path, settings.dry_run).await;
+    }
+}
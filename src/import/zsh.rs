@@ -0,0 +1,84 @@
+use super::Item;
+
+// zsh stores bytes >= 0x83 as 0x83 followed by the byte xor 32
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut meta = false;
+    for &b in bytes {
+        if meta {
+            out.push(b ^ 32);
+            meta = false;
+        } else if b == 0x83 {
+            meta = true;
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+// `: <epoch>:<seconds>;command`
+fn extended(line: &str) -> Option<(i64, i64, &str)> {
+    let rest = line.strip_prefix(": ")?;
+    let (stamp, command) = rest.split_once(';')?;
+    let (when, duration) = stamp.split_once(':')?;
+    Some((when.trim().parse().ok()?, duration.trim().parse().ok()?, command))
+}
+
+pub fn parse(contents: &[u8]) -> Vec<Item> {
+    let text = String::from_utf8_lossy(&unmetafy(contents)).into_owned();
+    let mut items = vec![];
+    let mut continued = false;
+    for line in text.split('\n') {
+        if continued {
+            let last: &mut Item = items.last_mut().unwrap();
+            last.command.push('\n');
+            last.command.push_str(line);
+        } else if let Some((when, duration, command)) = extended(line) {
+            items.push(Item {
+                command: command.to_string(),
+                when: Some(when),
+                duration_ms: Some(duration * 1000),
+                ..Default::default()
+            });
+        } else if !line.is_empty() {
+            items.push(Item {
+                command: line.to_string(),
+                ..Default::default()
+            });
+        } else {
+            continue;
+        }
+
+        // an embedded newline is written as a trailing backslash
+        let last = items.last_mut().unwrap();
+        continued = last.command.ends_with('\\');
+        if continued {
+            last.command.pop();
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_history() {
+        let items = parse(b": 1700000000:3;make -j8\n: 1700000010:0;for i in 1 2\\\ndo echo $i\\\ndone\nls\n");
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].command, "make -j8");
+        assert_eq!(items[0].when, Some(1700000000));
+        assert_eq!(items[0].duration_ms, Some(3000));
+        assert_eq!(items[1].command, "for i in 1 2\ndo echo $i\ndone");
+        assert_eq!(items[2].command, "ls");
+        assert_eq!(items[2].when, None);
+    }
+
+    #[test]
+    fn metafied() {
+        let items = parse(b": 1700000000:0;echo \xc4\x83\xa3\n");
+        assert_eq!(items[0].command, "echo \u{103}");
+    }
+}
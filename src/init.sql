@@ -8,5 +8,43 @@ CREATE TABLE IF NOT EXISTS {schema}.commands (
     when_run BIGINT NOT NULL,
     exit_code INTEGER NOT NULL DEFAULT 0
 );
-CREATE UNIQUE INDEX IF NOT EXISTS command_norm ON {schema}.commands (normalized);
-CREATE INDEX IF NOT EXISTS command_when ON {schema}.commands (when_run DESC);
+-- CREATE INDEX and ALTER TABLE lock the table even when there is nothing to
+-- do, which would stall every client behind a long writer, so they only run
+-- for a schema that lacks what they add
+DO $$
+BEGIN
+    IF to_regclass('{schema}.command_norm') IS NULL THEN
+        CREATE UNIQUE INDEX IF NOT EXISTS command_norm ON {schema}.commands (normalized);
+    END IF;
+    IF to_regclass('{schema}.command_when') IS NULL THEN
+        CREATE INDEX IF NOT EXISTS command_when ON {schema}.commands (when_run DESC);
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_schema = '{schema}' AND table_name = 'commands'
+                     AND column_name = 'duration_ms') THEN
+        ALTER TABLE {schema}.commands ADD COLUMN IF NOT EXISTS cwd TEXT;
+        ALTER TABLE {schema}.commands ADD COLUMN IF NOT EXISTS duration_ms BIGINT;
+    END IF;
+END $$;
+
+CREATE TABLE IF NOT EXISTS {schema}.meta (
+    key TEXT PRIMARY KEY,
+    value BIGINT NOT NULL
+);
+INSERT INTO {schema}.meta (key, value) VALUES ('pruned_at', 0) ON CONFLICT DO NOTHING;
+
+-- every spelling typed for a command; `commands.original` shows the most used
+CREATE TABLE IF NOT EXISTS {schema}.variants (
+    command_id INTEGER NOT NULL REFERENCES {schema}.commands (id) ON DELETE CASCADE,
+    spelling TEXT NOT NULL,
+    original TEXT NOT NULL,
+    cnt INTEGER NOT NULL DEFAULT 1,
+    when_run BIGINT NOT NULL,
+    PRIMARY KEY (command_id, spelling)
+);
+-- commands stored before start out with the one spelling known for them
+INSERT INTO {schema}.variants (command_id, spelling, original, cnt, when_run)
+SELECT id, original, original, cnt, when_run FROM {schema}.commands
+WHERE NOT EXISTS (SELECT 1 FROM {schema}.meta WHERE key = 'variants_seeded')
+ON CONFLICT DO NOTHING;
+INSERT INTO {schema}.meta (key, value) VALUES ('variants_seeded', 1) ON CONFLICT DO NOTHING;
@@ -18,11 +18,16 @@ use humantime::format_duration;
 use std::{
     cmp,
     collections::HashMap,
-    io::{stdout, Write},
+    fs::OpenOptions,
+    io::{stdout, BufWriter, IsTerminal, Write},
     string::String,
 };
 
 const PAGE_SIZE: usize = 100;
+// stands in for the newlines of a multi-line command in the candidate list
+const CONTINUATION: &str = "↵";
+// marks the other spellings listed under an expanded command
+const VARIANT: &str = "↳ ";
 
 pub struct Interface<'a> {
     settings: &'a Settings,
@@ -36,6 +41,7 @@ pub struct Interface<'a> {
     total_count: i64,
     has_more: bool,
     explain_cache: HashMap<i64, String>,
+    preview_rows: usize,
 }
 
 pub enum MoveSelection {
@@ -57,7 +63,7 @@ impl MenuMode {
             return String::from("Delete selected command from the history? (Y/N)");
         }
 
-        menu_text.push_str(" | ⏎ - Run | TAB - Edit | F2 - Delete");
+        menu_text.push_str(" | ⏎ - Run | TAB - Edit | F2 - Delete | F3 - Variants");
 
         menu_text
     }
@@ -84,6 +90,7 @@ impl<'a> Interface<'a> {
             total_count: 0,
             has_more: false,
             explain_cache: HashMap::new(),
+            preview_rows: 0,
         }
     }
 
@@ -116,7 +123,7 @@ impl<'a> Interface<'a> {
         if !self.has_more {
             return;
         }
-        let offset = self.matches.len() as i64;
+        let offset = self.matches.iter().filter(|m| !m.variant).count() as i64;
         let (more, _total) = crate::runtime().block_on(db::find_matches(
             &self.input.command,
             PAGE_SIZE as i64,
@@ -126,8 +133,8 @@ impl<'a> Interface<'a> {
             self.has_more = false;
         } else {
             self.total_count = _total;
+            self.has_more = offset + (more.len() as i64) < _total;
             self.matches.extend(more);
-            self.has_more = (self.matches.len() as i64) < _total;
         }
     }
 
@@ -248,8 +255,15 @@ impl<'a> Interface<'a> {
             return;
         }
 
-        let (mut top, mut bottom) = (self.offset, self.offset + rows - 1);
         self.selection = cmp::min(self.selection, if self.matches.is_empty() { 0 } else { self.matches.len() - 1 });
+
+        // a selected multi-line command is shown in full at the far end
+        let preview = self.preview_lines(rows);
+        let resized = resized || preview.len() != self.preview_rows;
+        self.preview_rows = preview.len();
+        let rows = rows - preview.len();
+
+        let (mut top, mut bottom) = (self.offset, self.offset + rows - 1);
         if resized {
             if self.selection > bottom {
                 self.offset = self.selection - rows;
@@ -305,6 +319,41 @@ impl<'a> Interface<'a> {
             )
             .unwrap();
         }
+
+        let first = if self.settings.bottom { min } else { max - preview.len() as i16 + 1 };
+        let (_, fg, _, bar) = Self::candidate_theme(self.settings.lightmode, false);
+        for (n, text) in preview.iter().enumerate() {
+            let text = FixedLengthGraphemeString::new(text.as_str(), cmp::max(width, 5) - 5);
+            queue!(
+                screen,
+                cursor::MoveTo(1, (first + n as i16) as u16),
+                SetForegroundColor(bar),
+                Print("│  "),
+                SetForegroundColor(fg),
+                Print(text.string),
+                SetForegroundColor(Color::Reset),
+            )
+            .unwrap();
+        }
+    }
+
+    fn multiline(&self, index: usize) -> bool {
+        self.matches.get(index).is_some_and(|m| m.cmd.contains('\n'))
+    }
+
+    // takes at most half of the result rows, the last one marks a cut
+    fn preview_lines(&self, rows: usize) -> Vec<String> {
+        if rows < 2 || !self.multiline(self.selection) {
+            return vec![];
+        }
+        let cmd = &self.matches[self.selection].cmd;
+        let room = rows / 2;
+        let mut lines: Vec<String> = cmd.lines().take(room + 1).map(str::to_owned).collect();
+        if lines.len() > room {
+            lines.truncate(room);
+            lines[room - 1] = String::from("...");
+        }
+        lines
     }
 
     fn move_selection(&mut self, direction: MoveSelection) {
@@ -352,6 +401,25 @@ impl<'a> Interface<'a> {
         }
     }
 
+    // lists the other spellings under the selected command, or folds them back
+    fn toggle_variants(&mut self) {
+        let mut head = self.selection;
+        while head > 0 && self.matches[head].variant {
+            head -= 1;
+        }
+        let end = (head + 1..self.matches.len())
+            .find(|&i| !self.matches[i].variant)
+            .unwrap_or(self.matches.len());
+        if end > head + 1 {
+            self.matches.drain(head + 1..end);
+            self.selection = head;
+            return;
+        }
+
+        let more = crate::runtime().block_on(db::variants(&self.matches[head].cmd, &self.input.command));
+        self.matches.splice(head + 1..head + 1, more);
+    }
+
     fn key_code_handler(&mut self, key_event: KeyEvent) -> bool {
         if let KeyEvent {
             modifiers: KeyModifiers::CONTROL,
@@ -389,10 +457,20 @@ impl<'a> Interface<'a> {
         self.has_more && self.selection + 10 >= self.matches.len()
     }
 
+    // when the caller captures stdout for the result, draw on the tty instead
+    fn screen() -> Box<dyn Write> {
+        if !stdout().is_terminal() {
+            if let Ok(tty) = OpenOptions::new().write(true).open("/dev/tty") {
+                return Box::new(BufWriter::new(tty));
+            }
+        }
+        Box::new(stdout())
+    }
+
     fn select(&mut self) {
         self.load_initial_matches();
 
-        let mut screen = stdout();
+        let mut screen = Self::screen();
         terminal::enable_raw_mode().unwrap();
         queue!(screen, EnterAlternateScreen).unwrap();
 
@@ -422,6 +500,7 @@ impl<'a> Interface<'a> {
                 Event::Key(key_event) => {
                     let cursor = self.input.cursor;
                     let menu = self.menu_mode;
+                    let listed = self.matches.len();
                     idx = self.selection as i32;
 
                     if self.key_code_handler(key_event) {
@@ -430,6 +509,13 @@ impl<'a> Interface<'a> {
 
                     if cursor != self.input.cursor
                         || menu != self.menu_mode
+                        || listed != self.matches.len()
+                    {
+                        idx = -1;
+                    }
+
+                    if self.selection as i32 != idx
+                        && (self.multiline(idx as usize) || self.multiline(self.selection))
                     {
                         idx = -1;
                     }
@@ -515,6 +601,13 @@ impl<'a> Interface<'a> {
                     self.menu_mode = MenuMode::ConfirmDelete;
                 }
 
+            KeyEvent {
+                code: KeyCode::F(3), ..
+            }
+                if !self.matches.is_empty() => {
+                    self.toggle_variants();
+                }
+
             _ => {}
         }
 
@@ -529,9 +622,13 @@ impl<'a> Interface<'a> {
         hl: Color,
         fg: Color,
     ) {
-        let max_grapheme_length = cmp::max(width - 14, 0);
+        let mut max_grapheme_length = cmp::max(width - 14, 0);
+        if command.variant {
+            execute!(screen, SetForegroundColor(fg), Print(VARIANT)).unwrap();
+            max_grapheme_length = max_grapheme_length.saturating_sub(2);
+        }
         let mut out1 = FixedLengthGraphemeString::empty(max_grapheme_length);
-        out1.push_grapheme_str(&command.cmd[..]);
+        out1.push_grapheme_str(command.cmd.replace('\n', CONTINUATION));
         if target.is_empty() {
             execute!(screen, SetForegroundColor(fg), Print(&out1.string)).unwrap();
             return;
@@ -539,7 +636,9 @@ impl<'a> Interface<'a> {
 
         let cmd = &out1.string;
         let mut prev: usize = 0;
-        for &(start, mut end) in &command.match_bounds {
+        let shift = |i: usize| i + command.cmd[..i].matches('\n').count() * (CONTINUATION.len() - 1);
+        for &(start, end) in &command.match_bounds {
+            let (start, mut end) = (shift(start), shift(end));
             if start >= cmd.len() {
                 break;
             }
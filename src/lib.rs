@@ -1,15 +1,23 @@
+pub mod backup;
 pub mod cli;
 pub mod command_input;
 pub mod conf;
+pub mod crypt;
 pub mod db;
+pub mod export;
 pub mod fixed_length_grapheme_string;
 pub mod history;
 pub mod history_cleaner;
+pub mod import;
 pub mod interface;
 pub mod normalize;
+pub mod renormalize;
+pub mod retention;
 pub mod settings;
 mod shell;
 pub mod shell_history;
+pub mod suggest;
+pub mod sync;
 
 use std::sync::OnceLock;
 
@@ -1,16 +1,61 @@
 use rhis::{
+    backup,
+    cli::ShellKind,
     conf,
+    crypt,
     db,
+    export,
+    history_cleaner,
+    import,
     interface::Interface,
+    renormalize,
+    retention,
     settings::{Mode, Settings},
+    suggest,
+    sync,
 };
 
 async fn handle_addition(settings: &Settings) {
-    db::save_command(
+    let saved = db::save_command(
         &settings.command,
         settings.exit_code,
+        settings.shell,
+        settings.duration_ms,
+        &settings.known,
     )
     .await;
+    match saved {
+        db::Saved::Stored => retention::after_save().await,
+        db::Saved::NotFound(command) => suggest::did_you_mean(&command, settings.shell).await,
+        db::Saved::Ignored => {}
+    }
+}
+
+async fn handle_list(settings: &Settings) {
+    use std::io::{stdout, Write};
+
+    let (matches, _) = db::find_matches(
+        &settings.command,
+        settings.limit.unwrap_or(i64::MAX),
+        settings.offset,
+    )
+    .await;
+
+    let mut out = stdout().lock();
+    for m in &matches {
+        let result = if settings.json {
+            serde_json::to_writer(&mut out, m)
+                .map_err(std::io::Error::from)
+                .and_then(|_| writeln!(out))
+        } else if settings.null_separated {
+            write!(out, "{}\0", m.cmd)
+        } else {
+            writeln!(out, "{}", m.cmd)
+        };
+        if result.is_err() {
+            break;
+        }
+    }
 }
 
 fn handle_search(settings: &Settings) {
@@ -23,6 +68,13 @@ fn handle_search(settings: &Settings) {
     let mut ui = Interface::new(settings, width, height);
     let Some(cmd) = ui.display() else { return };
 
+    if let Some(fd) = settings.output_fd {
+        use std::{fs::File, io::Write, os::fd::FromRawFd};
+        let mut out = unsafe { File::from_raw_fd(fd) };
+        _ = out.write_all(cmd.as_bytes());
+        return;
+    }
+
     for byte in cmd.as_bytes() {
         if unsafe { libc::ioctl(0, libc::TIOCSTI, byte) } < 0 {
             break;
@@ -40,7 +92,31 @@ async fn main() {
         .unwrap_or_else(|| shellexpand::tilde("~/.local/share/rhis/config.toml").into_owned());
     conf::conf_init(&config_path);
 
-    db::warmup();
+    // `forget` runs from every bash prompt and `init` from .bashrc; neither
+    // touches the database, so neither waits for it
+    // checked before the pool opens sockets that could take the number; what
+    // the runtime opened already is close-on-exec, a redirection never is
+    if let Some(fd) = settings.output_fd {
+        match unsafe { libc::fcntl(fd, libc::F_GETFD) } {
+            flags if flags < 0 => {
+                eprintln!("--fd {fd}: {}", std::io::Error::last_os_error());
+                return;
+            }
+            flags if flags & libc::FD_CLOEXEC != 0 => {
+                eprintln!("--fd {fd}: not redirected by the caller");
+                return;
+            }
+            _ => {}
+        }
+    }
+
+    if !matches!(settings.mode, Mode::Forget | Mode::Init) {
+        db::warmup();
+        if let Err(e) = crypt::ready() {
+            eprintln!("{e}");
+            return;
+        }
+    }
 
     match settings.mode {
         Mode::Add => {
@@ -51,28 +127,50 @@ async fn main() {
                 handle_search(&settings);
             });
         }
+        Mode::Import => {
+            import::run(&settings).await;
+        }
+        Mode::Export => {
+            export::run(&settings).await;
+        }
+        Mode::Backup => {
+            backup::backup(&settings).await;
+        }
+        Mode::Restore => {
+            backup::restore(&settings).await;
+        }
+        Mode::Prune => {
+            retention::run(&settings).await;
+        }
+        Mode::List => {
+            handle_list(&settings).await;
+        }
+        Mode::Renormalize => {
+            renormalize::run(&settings).await;
+        }
+        Mode::SyncHistfile => {
+            sync::run(&settings).await;
+        }
+        Mode::Forget => {
+            history_cleaner::forget(&settings);
+        }
         Mode::Init => {
-            let mut s: String = "".into();
-            let script = include_str!("../rhis.bash");
+            let mut script = match settings.shell {
+                ShellKind::Bash => include_str!("../rhis.bash"),
+                ShellKind::Zsh => include_str!("../rhis.zsh"),
+                ShellKind::Fish => include_str!("../rhis.fish"),
+            }
+            .to_string();
             if !settings.bottom {
-                let offset = script.find("--bottom").unwrap();
-                s = script.into();
-                s.replace_range(offset..offset + 9, "");
+                script = script.replacen("--bottom ", "", 1);
             }
-
             if !settings.lightmode {
-                let offset = script.find("--light").unwrap();
-                if s.is_empty() {
-                    s = script.into();
-                }
-                s.replace_range(offset..offset + 8, "");
+                script = script.replacen("--light ", "", 1);
             }
-
-            if s.is_empty() {
-                print!("{}", script);
-            } else {
-                print!("{}", s);
+            if settings.sync {
+                script = script.replacen("RHIS_SYNC=\n", "RHIS_SYNC=1\n", 1);
             }
+            print!("{}", script);
         }
     }
 }
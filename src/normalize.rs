@@ -1,86 +1,615 @@
-fn tokenize(command: &str) -> Vec<String> {
+use crate::conf::{self, CommandRule, NormalizeConfig};
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+
+// what a masked argument is replaced with
+const MASK: &str = "*";
+
+// tools known to parse their options with getopt, so `-la` means `-l -a`,
+// with the short options that take a value: `-XPOST` is `-X POST`
+const GETOPT: [(&str, &str); 30] = [
+    ("cat", ""),
+    ("chmod", ""),
+    ("chown", ""),
+    ("cp", "St"),
+    ("curl", "AbcCdDeEFHKmoPQrtTuUwxXyYz"),
+    ("cut", "bcdf"),
+    ("df", "Btx"),
+    ("diff", "CDFILSUWxX"),
+    ("du", "BdtX"),
+    ("free", "cs"),
+    ("grep", "ABCDdefm"),
+    ("head", "cn"),
+    ("less", "bhjkoOpPtTxyz"),
+    ("ln", "St"),
+    ("ls", "ITw"),
+    ("lsof", "cdDgikopsuFrT"),
+    ("mkdir", "m"),
+    ("mv", "St"),
+    ("netstat", "A"),
+    ("ps", "CGgOopqstUu"),
+    ("rm", ""),
+    ("rsync", "BefMT"),
+    ("scp", "cFiJloPS"),
+    ("sort", "koStT"),
+    ("ss", "AfFN"),
+    ("ssh", "BbcDEeFIiJLlmOopQRSWw"),
+    ("tail", "cns"),
+    ("tar", "bCfFgHIKLNTVX"),
+    ("uniq", "fsw"),
+    ("wc", ""),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Word,
+    // `|`, `&&`, `;`, a newline, `(`: ends one simple command, starts the next
+    Control,
+    // `>`, `2>>`, `<<<`, ...; `2>&1` and `>&-` carry their target along
+    Redirect,
+    // the word a redirection writes to or reads from
+    Target,
+    // the command name, and its subcommands when a rule says so
+    Head,
+    // the value split off `--opt=value` or `-XPOST`, which moves along with its option
+    Value,
+    // the lines of a here-document up to its delimiter, kept verbatim
+    Heredoc,
+}
+
+struct Token {
+    text: String,
+    // bytes of the original command the token came from, quotes included
+    span: Range<usize>,
+    kind: Kind,
+}
+
+fn needs_target(op: &str) -> bool {
+    op.ends_with(['<', '>', '|', '&']) || op.ends_with("<<-")
+}
+
+// where the here-documents opened on a line end: each one runs up to the
+// line holding just its delimiter, after leading tabs for `<<-`.
+fn heredoc_end(command: &str, mut at: usize, delimiters: &[(String, bool)]) -> usize {
+    let mut end = at;
+    for (delimiter, strip) in delimiters {
+        loop {
+            if at > command.len() {
+                return command.len();
+            }
+            let eol = command[at..].find('\n').map_or(command.len(), |n| at + n);
+            let line = &command[at..eol];
+            let line = if *strip { line.trim_start_matches('\t') } else { line };
+            (end, at) = (eol, eol + 1);
+            if line == delimiter {
+                break;
+            }
+        }
+    }
+    end
+}
+
+// operators are only recognized outside quotes; `$(...)`, `<(...)` and
+// backquotes are kept verbatim, spaces included, as part of their word.
+fn tokenize(command: &str) -> Vec<Token> {
+    let chars: Vec<(usize, char)> = command.char_indices().collect();
+    let next = |i: usize| chars.get(i + 1).map(|&(_, c)| c);
+    let offset = |i: usize| chars.get(i).map_or(command.len(), |&(o, _)| o);
+
     let mut tokens = vec![];
     let mut buf = String::new();
+    let mut start = None;
+    let mut quoted = false;
     let mut in_single = false;
     let mut in_double = false;
     let mut escaped = false;
+    let mut depth = 0;
+    let mut backquote = false;
+    // `<<` and `<<-` redirections whose here-document starts after this line
+    let mut heredocs = vec![];
+
+    let flush = |tokens: &mut Vec<Token>, buf: &mut String, start: &mut Option<usize>, end: usize| {
+        if let Some(start) = start.take() {
+            if !buf.is_empty() {
+                tokens.push(Token {
+                    text: std::mem::take(buf),
+                    span: start..end,
+                    kind: Kind::Word,
+                });
+            }
+        }
+        buf.clear();
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let (o, c) = chars[i];
+        i += 1;
 
-    for c in command.chars() {
+        if depth > 0 || backquote {
+            buf.push(c);
+            match c {
+                '(' if depth > 0 => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                '`' if backquote && !buf.ends_with("\\`") => backquote = false,
+                _ => {}
+            }
+            continue;
+        }
+        if escaped {
+            escaped = false;
+            buf.push(c);
+            continue;
+        }
+
+        let opens = matches!(c, '$' | '<' | '>') && next(i - 1) == Some('(');
+        if opens && (!in_single && (c == '$' || !in_double)) {
+            start.get_or_insert(o);
+            buf.push(c);
+            buf.push('(');
+            depth = 1;
+            i += 1;
+            continue;
+        }
         match c {
+            // a line continuation, gone before bash splits words
+            '\\' if !in_single && next(i - 1) == Some('\n') => {
+                i += 1;
+                continue;
+            }
             '\\' if !in_single => {
-                if escaped {
-                    buf.push('\\');
-                }
-                escaped = !escaped;
+                start.get_or_insert(o);
+                quoted = true;
+                escaped = true;
+                continue;
             }
-            '\'' if !escaped && !in_double => {
+            '\'' if !in_double => {
+                start.get_or_insert(o);
+                quoted = true;
                 in_single = !in_single;
+                continue;
             }
-            '"' if !escaped && !in_single => {
+            '"' if !in_single => {
+                start.get_or_insert(o);
+                quoted = true;
                 in_double = !in_double;
+                continue;
             }
-            ' ' | '\t' if !in_single && !in_double && !escaped => {
-                if !buf.is_empty() {
-                    tokens.push(buf.clone());
-                    buf.clear();
+            '`' if !in_single => {
+                start.get_or_insert(o);
+                buf.push(c);
+                backquote = true;
+                continue;
+            }
+            _ if in_single || in_double => {
+                buf.push(c);
+                continue;
+            }
+            _ => {}
+        }
+
+        let control = |text: &str, len: usize| Token {
+            text: text.to_string(),
+            span: o..offset(i - 1 + len),
+            kind: Kind::Control,
+        };
+        match c {
+            ' ' | '\t' => {
+                flush(&mut tokens, &mut buf, &mut start, o);
+                quoted = false;
+            }
+            '\n' if !heredocs.is_empty() => {
+                flush(&mut tokens, &mut buf, &mut start, o);
+                quoted = false;
+                let delimiters: Vec<(String, bool)> = std::mem::take(&mut heredocs)
+                    .into_iter()
+                    .filter_map(|r: usize| {
+                        let strip = tokens[r].text.ends_with('-');
+                        Some((tokens.get(r + 1)?.text.clone(), strip))
+                    })
+                    .collect();
+                let end = heredoc_end(command, offset(i), &delimiters);
+                tokens.push(Token {
+                    text: command[o..end].to_string(),
+                    span: o..end,
+                    kind: Kind::Heredoc,
+                });
+                // the newline after the last delimiter ends the command
+                while offset(i) < end {
+                    i += 1;
                 }
             }
-            _ => {
-                if escaped {
-                    escaped = false;
+            '\n' | ';' | '|' | ')' | '&' if !(c == '&' && next(i - 1) == Some('>')) => {
+                flush(&mut tokens, &mut buf, &mut start, o);
+                quoted = false;
+                let op = match (c, next(i - 1)) {
+                    ('\n', _) => ";",
+                    (';', Some(';')) => ";;",
+                    (';', Some('&')) => ";&",
+                    ('|', Some('|')) => "||",
+                    ('|', Some('&')) => "|&",
+                    ('&', Some('&')) => "&&",
+                    _ => &command[o..o + 1],
+                };
+                let len = if c == '\n' { 1 } else { op.len() };
+                tokens.push(control(op, len));
+                i += len - 1;
+            }
+            '(' if start.is_none() => tokens.push(control("(", 1)),
+            '<' | '>' | '&' => {
+                // a bare number right before the operator is its descriptor
+                let from = match start {
+                    Some(s) if !quoted && buf.bytes().all(|b| b.is_ascii_digit()) => {
+                        start = None;
+                        s
+                    }
+                    _ => {
+                        flush(&mut tokens, &mut buf, &mut start, o);
+                        o
+                    }
+                };
+                let mut op = std::mem::take(&mut buf);
+                quoted = false;
+                op.push(c);
+                let follows: &[&str] = match c {
+                    '>' => &[">", "|", "&"],
+                    '<' => &["<<", "<", "&", ">"],
+                    _ => &[">>", ">"],
+                };
+                for f in follows {
+                    if command[offset(i)..].starts_with(f) {
+                        op.push_str(f);
+                        i += f.len();
+                        break;
+                    }
+                }
+                let heredoc = op.ends_with("<<") && !op.ends_with("<<<");
+                if heredoc && next(i - 1) == Some('-') {
+                    op.push('-');
+                    i += 1;
+                }
+                if heredoc {
+                    heredocs.push(tokens.len());
                 }
+                if op.ends_with('&') {
+                    while let Some(d) = chars.get(i).map(|&(_, c)| c).filter(|c| c.is_ascii_digit() || *c == '-') {
+                        op.push(d);
+                        i += 1;
+                    }
+                }
+                tokens.push(Token {
+                    text: op,
+                    span: from..offset(i),
+                    kind: Kind::Redirect,
+                });
+            }
+            _ => {
+                start.get_or_insert(o);
                 buf.push(c);
             }
         }
     }
-    if !buf.is_empty() {
-        tokens.push(buf);
-    }
+    flush(&mut tokens, &mut buf, &mut start, command.len());
     tokens
 }
 
+fn config() -> Option<&'static NormalizeConfig> {
+    conf::conf_try_get()?.normalize.as_ref()
+}
+
+// the options of a getopt tool that take a value; a tool listed in the
+// config but not known here is taken to have none
+fn getopt(config: Option<&NormalizeConfig>, name: &str) -> Option<&'static str> {
+    let known = GETOPT.iter().find(|g| g.0 == name).map(|g| g.1);
+    match config.and_then(|c| c.getopt.as_ref()) {
+        Some(names) => names.iter().any(|n| n == name).then(|| known.unwrap_or("")),
+        None => known,
+    }
+}
+
+// `$HOME/x`, `${HOME}/x` and `/home/me/x` are all `~/x`; leading `./` is dropped
+fn canonical_path(text: &str, home: &str) -> String {
+    for prefix in ["$HOME", "${HOME}", home] {
+        if prefix.len() < 2 {
+            continue;
+        }
+        if let Some(rest) = text.strip_prefix(prefix).filter(|r| r.is_empty() || r.starts_with('/')) {
+            return format!("~{rest}");
+        }
+    }
+    let mut rest = text;
+    while let Some(r) = rest.strip_prefix("./").filter(|r| !r.is_empty()) {
+        rest = r;
+    }
+    rest.to_string()
+}
+
+// marks the command name, and as many subcommands as its rule asks for;
+// a rule for `git commit` then takes over from the one for `git`. The
+// values of the options the rule lists (`git -C dir`) are not subcommands.
+fn rule_for<'a>(
+    config: Option<&'a NormalizeConfig>,
+    tokens: &mut [Token],
+    arg0: usize,
+    name: &str,
+) -> Option<&'a CommandRule> {
+    let rules = &config?.commands;
+    let rule = rules.get(name);
+    let mut key = name.to_string();
+    let subcommands = rule.and_then(|r| r.subcommands).unwrap_or(0);
+    let options = rule.map_or(&[][..], |r| &r.options[..]);
+    let mut value = false;
+    let mut taken = 0;
+    for t in tokens[arg0 + 1..].iter_mut().filter(|t| t.kind == Kind::Word) {
+        if taken == subcommands {
+            break;
+        }
+        if is_flag(&t.text) {
+            value = options.contains(&t.text);
+            continue;
+        }
+        if std::mem::take(&mut value) {
+            continue;
+        }
+        t.kind = Kind::Head;
+        key.push(' ');
+        key.push_str(&t.text);
+        taken += 1;
+    }
+    rules.get(&key).or(rule)
+}
+
+// a piece of `token`; exact when the token was written without quoting,
+// otherwise it can only point at the whole token.
+fn piece(command: &str, token: &Token, text: String, at: Range<usize>) -> Token {
+    let span = if command[token.span.clone()] == token.text {
+        token.span.start + at.start..token.span.start + at.end
+    } else {
+        token.span.clone()
+    };
+    Token {
+        text,
+        span,
+        kind: Kind::Word,
+    }
+}
+
+// `-la` becomes `-l -a` for getopt tools, up to the first option that
+// takes a value, which keeps the rest as its value (`-lroot` is `-l root`);
+// `--opt=value` becomes `--opt value` everywhere. Nothing after `--` is touched.
+fn expand(command: &str, tokens: Vec<Token>, valued: Option<&str>) -> Vec<Token> {
+    let mut result = Vec::with_capacity(tokens.len());
+    let mut options = true;
+    for t in tokens {
+        let text = t.text.as_str();
+        if t.kind != Kind::Word || !options || !is_flag(text) {
+            options &= text != "--";
+        } else if let Some((name, value)) = text.strip_prefix("--").and_then(|l| l.split_once('=')) {
+            if !name.is_empty() {
+                let eq = name.len() + 2;
+                result.push(piece(command, &t, text[..eq].to_string(), 0..eq));
+                let mut value = piece(command, &t, value.to_string(), eq + 1..text.len());
+                value.kind = Kind::Value;
+                result.push(value);
+                continue;
+            }
+        } else if let Some(valued) = valued.filter(|_| {
+            !text.starts_with("--")
+                && text.len() > 2
+                && text[1..].chars().all(|c| c.is_ascii_alphabetic())
+        }) {
+            for i in 1..text.len() {
+                // the leading dash goes with the first flag
+                let at = if i == 1 { 0..2 } else { i..i + 1 };
+                result.push(piece(command, &t, format!("-{}", &text[i..i + 1]), at));
+                if valued.contains(&text[i..i + 1]) {
+                    if i + 1 < text.len() {
+                        let rest = text[i + 1..].to_string();
+                        let mut value = piece(command, &t, rest, i + 1..text.len());
+                        value.kind = Kind::Value;
+                        result.push(value);
+                    }
+                    break;
+                }
+            }
+            continue;
+        }
+        result.push(t);
+    }
+    result
+}
+
+// drops ignored options along with their value, and masks arguments
+// that change from run to run.
+fn apply(rule: &CommandRule, tokens: Vec<Token>) -> Vec<Token> {
+    let mut result = Vec::with_capacity(tokens.len());
+    let mut value = false;
+    for mut t in tokens {
+        if !matches!(t.kind, Kind::Word | Kind::Value) {
+            value = false;
+            result.push(t);
+            continue;
+        }
+        if std::mem::take(&mut value) && !is_flag(&t.text) {
+            continue;
+        }
+        if rule.ignore.contains(&t.text) {
+            value = true;
+            continue;
+        }
+        // `-mmsg`, a short option written together with its value
+        let attached = !t.text.starts_with("--") && t.text.len() > 2 && rule.ignore.iter().any(|o| o.len() == 2 && t.text.starts_with(o.as_str()));
+        if attached {
+            continue;
+        }
+        if rule.mask.iter().any(|m| m.is_match(&t.text)) {
+            t.text = MASK.to_string();
+        }
+        result.push(t);
+    }
+    result
+}
+
 fn is_flag(t: &str) -> bool {
     (t.starts_with('-') || t.starts_with("--")) && t != "-" && t != "--" && !t.starts_with("---") && t.len() > 1
 }
 
-pub fn normalize(command: &str) -> String {
-    let trimmed = command.trim();
-    if trimmed.is_empty() {
-        return String::new();
+fn sorted(flags: &mut Vec<Vec<Token>>) -> Vec<Token> {
+    flags.sort_by(|a, b| a.iter().map(|t| &t.text).cmp(b.iter().map(|t| &t.text)));
+    std::mem::take(flags).into_iter().flatten().collect()
+}
+
+// flags between two positionals are sorted, except the last one, which
+// may take the positional as its value. A value split off `--opt=value`
+// stays with its option. Redirections stay where they are and end a run
+// of flags.
+fn reorder(tokens: Vec<Token>) -> Vec<Token> {
+    let mut result = vec![];
+    let mut flag_buf: Vec<Vec<Token>> = vec![];
+
+    for t in tokens {
+        match t.kind {
+            Kind::Word if is_flag(&t.text) => flag_buf.push(vec![t]),
+            Kind::Value if !flag_buf.is_empty() => flag_buf.last_mut().unwrap().push(t),
+            Kind::Word | Kind::Head | Kind::Value => {
+                if let Some(last) = flag_buf.pop() {
+                    result.append(&mut sorted(&mut flag_buf));
+                    result.extend(last);
+                }
+                result.push(t);
+            }
+            _ => {
+                result.append(&mut sorted(&mut flag_buf));
+                result.push(t);
+            }
+        }
+    }
+
+    result.append(&mut sorted(&mut flag_buf));
+    result
+}
+
+// one simple command of a pipeline or list
+fn simple(config: Option<&NormalizeConfig>, command: &str, mut tokens: Vec<Token>) -> Vec<Token> {
+    let mut target = false;
+    for t in &mut tokens {
+        match t.kind {
+            Kind::Redirect => target = needs_target(&t.text),
+            Kind::Word if target => {
+                t.kind = Kind::Target;
+                target = false;
+            }
+            _ => {}
+        }
     }
-    let tokens = tokenize(trimmed);
-    if tokens.is_empty() {
-        return String::new();
+    let Some(arg0) = tokens.iter().position(|t| t.kind == Kind::Word) else {
+        return tokens;
+    };
+    tokens[arg0].kind = Kind::Head;
+    let name = tokens[arg0].text.rsplit('/').next().unwrap_or_default().to_string();
+
+    let rule = rule_for(config, &mut tokens, arg0, &name);
+    let mut tokens = expand(command, tokens, getopt(config, &name));
+    if config.is_some_and(|c| c.paths) {
+        let home = shellexpand::tilde("~");
+        for t in tokens
+            .iter_mut()
+            .filter(|t| !matches!(t.kind, Kind::Redirect | Kind::Heredoc) && !is_flag(&t.text))
+        {
+            t.text = canonical_path(&t.text, &home);
+        }
+    }
+    if let Some(rule) = rule {
+        tokens = apply(rule, tokens);
     }
+    match rule.and_then(|r| r.sort) {
+        Some(false) => tokens,
+        _ => reorder(tokens),
+    }
+}
 
-    let mut result = vec![tokens[0].clone()];
-    let mut flag_buf: Vec<&str> = vec![];
+pub struct Normalized {
+    pub text: String,
+    // (range in `text`, range in the original command) for every token
+    pub spans: Vec<(Range<usize>, Range<usize>)>,
+}
 
-    for t in &tokens[1..] {
-        if is_flag(t) {
-            flag_buf.push(t);
-        } else {
-            if !flag_buf.is_empty() {
-                let last = flag_buf.pop().unwrap();
-                flag_buf.sort_unstable();
-                for f in &flag_buf {
-                    result.push(f.to_string());
+impl Normalized {
+    // where each occurrence of `needle` in the normalized text came from in
+    // the original: sorted, merged byte ranges. A match inside a token that
+    // was written as is maps exactly, otherwise the whole token is taken.
+    pub fn bounds(&self, original: &str, needle: &str) -> Vec<(usize, usize)> {
+        let mut bounds = vec![];
+        if needle.is_empty() {
+            return bounds;
+        }
+        for (at, _) in self.text.match_indices(needle) {
+            let hit = at..at + needle.len();
+            for (norm, orig) in &self.spans {
+                if norm.end <= hit.start || hit.end <= norm.start {
+                    continue;
+                }
+                if original[orig.clone()] == self.text[norm.clone()] {
+                    let from = hit.start.max(norm.start) - norm.start;
+                    let to = hit.end.min(norm.end) - norm.start;
+                    bounds.push((orig.start + from, orig.start + to));
+                } else {
+                    bounds.push((orig.start, orig.end));
                 }
-                result.push(last.to_string());
-                flag_buf.clear();
             }
-            result.push(t.clone());
         }
+        bounds.sort_unstable();
+        bounds.dedup();
+        let mut merged: Vec<(usize, usize)> = vec![];
+        for (start, end) in bounds {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
     }
+}
+
+pub fn normalize_mapped(command: &str) -> Normalized {
+    mapped_with(config(), command)
+}
 
-    if !flag_buf.is_empty() {
-        flag_buf.sort_unstable();
-        for f in &flag_buf {
-            result.push(f.to_string());
+fn mapped_with(config: Option<&NormalizeConfig>, command: &str) -> Normalized {
+    let mut normalized = Normalized {
+        text: String::new(),
+        spans: vec![],
+    };
+    let mut tokens = vec![];
+    let mut segment = vec![];
+    for t in tokenize(command) {
+        if t.kind == Kind::Control {
+            tokens.append(&mut simple(config, command, std::mem::take(&mut segment)));
+            tokens.push(t);
+        } else {
+            segment.push(t);
         }
     }
+    tokens.append(&mut simple(config, command, segment));
 
-    result.join(" ")
+    let nfc = config.is_some_and(|c| c.nfc);
+    for mut t in tokens {
+        if nfc {
+            t.text = t.text.nfc().collect();
+        }
+        // a here-document starts with the newline that ends its first line
+        if !normalized.text.is_empty() && t.kind != Kind::Heredoc {
+            normalized.text.push(' ');
+        }
+        let at = normalized.text.len();
+        normalized.text.push_str(&t.text);
+        normalized.spans.push((at..normalized.text.len(), t.span));
+    }
+    normalized
+}
+
+pub fn normalize(command: &str) -> String {
+    normalize_mapped(command).text
 }
 
 #[cfg(test)]
@@ -89,14 +618,14 @@ mod tests {
 
     #[test]
     fn whitespace_collapse() {
-        assert_eq!(normalize("ls   -la   -sa"), "ls -la -sa");
-        assert_eq!(normalize("  ls  -la  "), "ls -la");
+        assert_eq!(normalize("cmd   -la   -sa"), "cmd -la -sa");
+        assert_eq!(normalize("  cmd  -la  "), "cmd -la");
     }
 
     #[test]
     fn flag_sorting() {
-        assert_eq!(normalize("ls -sa -la"), "ls -la -sa");
-        assert_eq!(normalize("ls -b -a -c"), "ls -a -b -c");
+        assert_eq!(normalize("cmd -sa -la"), "cmd -la -sa");
+        assert_eq!(normalize("cmd -b -a -c"), "cmd -a -b -c");
     }
 
     #[test]
@@ -115,7 +644,130 @@ mod tests {
 
     #[test]
     fn single_flag() {
-        assert_eq!(normalize("ls -la"), "ls -la");
+        assert_eq!(normalize("cmd -la"), "cmd -la");
         assert_eq!(normalize("git commit -m msg"), "git commit -m msg");
     }
+
+    #[test]
+    fn multiline() {
+        assert_eq!(normalize("for i in 1 2\ndo echo $i\ndone"), "for i in 1 2 ; do echo $i ; done");
+        assert_eq!(normalize("echo 'a\nb'"), "echo a\nb");
+        assert_eq!(normalize("echo a\\\nb"), "echo ab");
+        assert_eq!(normalize("make \\\n  -k \\\n  all"), "make -k all");
+        assert_eq!(normalize("echo \"a\\\nb\" 'c\\\nd'"), "echo ab c\\\nd");
+    }
+
+    #[test]
+    fn bounds_follow_reordering() {
+        let original = "cmd  -sa -la";
+        let n = normalize_mapped(original);
+        assert_eq!(n.text, "cmd -la -sa");
+        assert_eq!(n.bounds(original, "cmd -la"), [(0, 3), (9, 12)]);
+        assert_eq!(n.bounds(original, "a -s"), [(5, 7), (11, 12)]);
+
+        let original = "echo 'a b' x";
+        let n = normalize_mapped(original);
+        assert_eq!(n.bounds(original, "a"), [(5, 10)]);
+        assert_eq!(n.bounds(original, "x"), [(11, 12)]);
+    }
+
+    #[test]
+    fn split_flags() {
+        assert_eq!(normalize("ls -la"), normalize("ls -l -a"));
+        assert_eq!(normalize("tar -xzf a.tgz"), "tar -x -z -f a.tgz");
+        assert_eq!(normalize("grep -A3 x"), "grep -A3 x");
+        assert_eq!(normalize("git commit -am msg"), "git commit -am msg");
+        assert_eq!(normalize("cmd --output=x"), normalize("cmd --output x"));
+        assert_eq!(normalize("cmd -- --a=b"), "cmd -- --a=b");
+        assert_eq!(normalize("cmd --b=1 --a=2"), normalize("cmd --a=2 --b=1"));
+        assert_eq!(normalize("cmd -x --b=1 --a=2 f"), "cmd --b 1 -x --a 2 f");
+        assert_eq!(normalize("curl -XPOST url"), "curl -X POST url");
+        assert_eq!(normalize("curl -sXPOST url"), "curl -s -X POST url");
+        assert_ne!(normalize("curl -XPOST url"), normalize("curl -XOPST url"));
+        assert_eq!(normalize("ssh -lroot host"), "ssh -l root host");
+        assert_eq!(normalize("grep -ifoo x"), "grep -i -f oo x");
+        assert_eq!(normalize("cut -dx -f1 f"), "cut -d x -f1 f");
+
+        let original = "ls -la";
+        let n = normalize_mapped(original);
+        assert_eq!(n.text, "ls -a -l");
+        assert_eq!(n.bounds(original, "-a -l"), [(3, 6)]);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(normalize("a -y -x | b -y -x"), "a -x -y | b -x -y");
+        assert_eq!(normalize("make -k&&make install||echo failed"), "make -k && make install || echo failed");
+        assert_eq!(normalize("cmd -b -a >out"), "cmd -a -b > out");
+        assert_eq!(normalize("cmd -b 2>/dev/null -a x"), "cmd -b 2> /dev/null -a x");
+        assert_eq!(normalize("make 2>&1 | tee log"), "make 2>&1 | tee log");
+        assert_eq!(normalize("cmd &> log &"), "cmd &> log &");
+        assert_eq!(normalize("echo $(ls -b -a) -d -c"), "echo $(ls -b -a) -c -d");
+        assert_eq!(normalize("diff <(sort a) <(sort b)"), "diff <(sort a) <(sort b)");
+        assert_eq!(normalize("echo 'a | b' \"c;d\""), "echo a | b c;d");
+        assert_eq!(normalize("(cd x; ls -la)"), "( cd x ; ls -a -l )");
+        assert_eq!(normalize("cat <<EOF\nx -b -a\nEOF"), "cat << EOF\nx -b -a\nEOF");
+        assert_ne!(normalize("cat <<EOF\nx -b -a\nEOF"), normalize("cat <<EOF\nx -a -b\nEOF"));
+        assert_eq!(
+            normalize("cat <<-'E' >f -b -a\n\tx  y\n\tE\nls -b -a"),
+            "cat <<- E > f -a -b\n\tx  y\n\tE ; ls -a -b"
+        );
+        assert_eq!(normalize("cat <<A <<B\na\nA\nb\nB"), "cat << A << B\na\nA\nb\nB");
+        assert_eq!(normalize("cat <<EOF\nno end"), "cat << EOF\nno end");
+        assert_eq!(normalize("cat <<< 'a b'"), "cat <<< a b");
+    }
+
+    #[test]
+    fn command_rules() {
+        let rule = CommandRule {
+            sort: None,
+            subcommands: None,
+            options: vec![],
+            ignore: vec!["-m".into(), "--message".into()],
+            mask: vec![regex::Regex::new("^[0-9a-f]{7,40}$").unwrap()],
+        };
+        let texts = |cmd: &str| -> Vec<String> {
+            apply(&rule, tokenize(cmd)).into_iter().map(|t| t.text).collect()
+        };
+        assert_eq!(texts("git commit -a -m 'fix it' -s"), ["git", "commit", "-a", "-s"]);
+        assert_eq!(texts("git commit -mfix"), ["git", "commit"]);
+        assert_eq!(texts("git show abc1234"), ["git", "show", "*"]);
+    }
+
+    #[test]
+    fn rules_through_normalize() {
+        let config: NormalizeConfig = toml::from_str(
+            r#"
+            [commands.git]
+            subcommands = 1
+            options = ["-C", "-c"]
+
+            [commands."git commit"]
+            ignore = ["-m", "--message"]
+
+            [commands.find]
+            sort = false
+            "#,
+        )
+        .unwrap();
+        let normalize = |cmd: &str| mapped_with(Some(&config), cmd).text;
+        assert_eq!(normalize("git commit -m x -a"), "git commit -a");
+        assert_eq!(normalize("git -C repo commit -m x -a"), "git -C repo commit -a");
+        assert_eq!(normalize("git -c a=b commit --message=y"), "git -c a=b commit");
+        assert_eq!(normalize("git -C repo status -s"), "git -C repo status -s");
+        assert_eq!(normalize("find . -print -delete"), "find . -print -delete");
+        assert_eq!(normalize("cmd . -print -delete"), "cmd . -delete -print");
+    }
+
+    #[test]
+    fn paths() {
+        let home = "/home/me";
+        assert_eq!(canonical_path("$HOME/bin/x.sh", home), "~/bin/x.sh");
+        assert_eq!(canonical_path("${HOME}", home), "~");
+        assert_eq!(canonical_path("/home/me/bin", home), "~/bin");
+        assert_eq!(canonical_path("/home/meg/bin", home), "/home/meg/bin");
+        assert_eq!(canonical_path("././script.sh", home), "script.sh");
+        assert_eq!(canonical_path("./", home), "./");
+        assert_eq!(canonical_path("../x", home), "../x");
+    }
 }
@@ -0,0 +1,234 @@
+use crate::{conf, crypt, db, normalize, settings::Settings};
+use sqlx::{postgres::PgRow, PgConnection, Row as _};
+use std::collections::HashMap;
+
+const BATCH: i64 = 1000;
+
+struct Row {
+    id: i32,
+    // the key as stored, and as normalize computes it now
+    stored: String,
+    key: String,
+    cnt: i32,
+    when_run: i64,
+}
+
+#[derive(Default)]
+struct Plan {
+    scanned: usize,
+    rekeyed: usize,
+    merged: usize,
+    // merged id, the id it folds into
+    deletes: Vec<(i32, i32)>,
+    // id, new key, new cnt
+    updates: Vec<(i32, String, i32)>,
+}
+
+// rows sharing a key fold into the most recently run one, which takes over
+// their spellings and gets the summed count.
+fn plan(rows: Vec<Row>) -> Plan {
+    let mut plan = Plan {
+        scanned: rows.len(),
+        ..Default::default()
+    };
+    let mut groups: HashMap<String, Vec<Row>> = HashMap::new();
+    for row in rows {
+        groups.entry(row.key.clone()).or_default().push(row);
+    }
+
+    for (key, mut group) in groups {
+        group.sort_by_key(|r| (r.when_run, r.id));
+        let winner = group.pop().unwrap();
+        let cnt = winner.cnt + group.iter().map(|r| r.cnt).sum::<i32>();
+        plan.rekeyed += usize::from(winner.stored != key) + group.iter().filter(|r| r.stored != key).count();
+        plan.merged += group.len();
+        plan.deletes.extend(group.iter().map(|r| (r.id, winner.id)));
+        if winner.stored != key || !group.is_empty() {
+            plan.updates.push((winner.id, key, cnt));
+        }
+    }
+    plan
+}
+
+// rows that cannot be decrypted with the current key are left alone
+fn row_from(r: &PgRow) -> Option<Row> {
+    let original = crypt::open(r.get(1))?;
+    Some(Row {
+        id: r.get(0),
+        stored: r.get(2),
+        key: crypt::digest(&normalize::normalize(&original)),
+        cnt: r.get(3),
+        when_run: r.get(4),
+    })
+}
+
+async fn load(conn: &mut PgConnection) -> Result<Vec<Row>, sqlx::Error> {
+    let schema = &conf::conf_get().database.schema;
+    let sql = format!(
+        "SELECT id, original, normalized, cnt, when_run FROM {schema}.commands \
+         WHERE id > $1 ORDER BY id LIMIT $2"
+    );
+    let mut rows = vec![];
+    let mut after = 0;
+    loop {
+        let batch = sqlx::query(&sql)
+            .bind(after)
+            .bind(BATCH)
+            .fetch_all(&mut *conn)
+            .await?;
+        let Some(last) = batch.last() else { break };
+        after = last.get(0);
+        rows.extend(batch.iter().filter_map(row_from));
+    }
+    Ok(rows)
+}
+
+// under the lock, only rows added or changed since the snapshot are read
+// and normalized again; rows deleted since drop out.
+async fn recheck(conn: &mut PgConnection, snapshot: Vec<Row>) -> Result<Vec<Row>, sqlx::Error> {
+    let schema = &conf::conf_get().database.schema;
+    let current: Vec<(i32, String, i32, i64)> = sqlx::query_as(&format!(
+        "SELECT id, normalized, cnt, when_run FROM {schema}.commands"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut known: HashMap<i32, Row> = snapshot.into_iter().map(|r| (r.id, r)).collect();
+    let mut rows = vec![];
+    let mut stale = vec![];
+    for (id, stored, cnt, when_run) in current {
+        match known.remove(&id) {
+            Some(r) if r.stored == stored && r.cnt == cnt && r.when_run == when_run => rows.push(r),
+            _ => stale.push(id),
+        }
+    }
+    let sql = format!(
+        "SELECT id, original, normalized, cnt, when_run FROM {schema}.commands WHERE id = ANY($1)"
+    );
+    for ids in stale.chunks(BATCH as usize) {
+        let batch = sqlx::query(&sql).bind(ids).fetch_all(&mut *conn).await?;
+        rows.extend(batch.iter().filter_map(row_from));
+    }
+    Ok(rows)
+}
+
+async fn apply(conn: &mut PgConnection, plan: &Plan) -> Result<(), sqlx::Error> {
+    let schema = &conf::conf_get().database.schema;
+    let losers: Vec<i32> = plan.deletes.iter().map(|d| d.0).collect();
+    let winners: Vec<i32> = plan.deletes.iter().map(|d| d.1).collect();
+    sqlx::query(&format!(
+        "INSERT INTO {schema}.variants (command_id, spelling, original, cnt, when_run) \
+         SELECT u.winner, v.spelling, MIN(v.original), SUM(v.cnt), MAX(v.when_run) \
+         FROM {schema}.variants v JOIN UNNEST($1::int[], $2::int[]) AS u(loser, winner) \
+             ON v.command_id = u.loser \
+         GROUP BY u.winner, v.spelling \
+         ON CONFLICT (command_id, spelling) DO UPDATE SET \
+             cnt = {schema}.variants.cnt + EXCLUDED.cnt, \
+             when_run = GREATEST({schema}.variants.when_run, EXCLUDED.when_run)"
+    ))
+    .bind(&losers)
+    .bind(&winners)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!("DELETE FROM {schema}.commands WHERE id = ANY($1)"))
+        .bind(&losers)
+        .execute(&mut *conn)
+        .await?;
+
+    // keys may move between rows, so they all step aside first
+    let ids: Vec<i32> = plan.updates.iter().map(|u| u.0).collect();
+    sqlx::query(&format!(
+        "UPDATE {schema}.commands SET normalized = 'renormalize:' || id WHERE id = ANY($1)"
+    ))
+    .bind(&ids)
+    .execute(&mut *conn)
+    .await?;
+
+    for chunk in plan.updates.chunks(BATCH as usize) {
+        let ids: Vec<i32> = chunk.iter().map(|u| u.0).collect();
+        let keys: Vec<&str> = chunk.iter().map(|u| u.1.as_str()).collect();
+        let cnts: Vec<i32> = chunk.iter().map(|u| u.2).collect();
+        sqlx::query(&format!(
+            "UPDATE {schema}.commands c SET normalized = u.key, cnt = u.cnt \
+             FROM UNNEST($1::int[], $2::text[], $3::int[]) AS u(id, key, cnt) WHERE c.id = u.id"
+        ))
+        .bind(ids)
+        .bind(keys)
+        .bind(cnts)
+        .execute(&mut *conn)
+        .await?;
+    }
+    db::refresh_originals(conn, &ids).await
+}
+
+// the slow part, decrypting and normalizing every row, reads a snapshot
+// while `rhis add` goes on as usual
+async fn snapshot() -> Result<Vec<Row>, sqlx::Error> {
+    let mut tx = db::pg_pool().begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let rows = load(&mut tx).await?;
+    tx.commit().await?;
+    Ok(rows)
+}
+
+// applying runs under a lock that keeps `rhis add` waiting, so nothing is
+// inserted between the recheck and rewriting the keys.
+async fn renormalize(dry_run: bool) -> Result<Plan, sqlx::Error> {
+    let rows = snapshot().await?;
+    if dry_run {
+        return Ok(plan(rows));
+    }
+    let schema = &conf::conf_get().database.schema;
+    let mut tx = db::pg_pool().begin().await?;
+    sqlx::query(&format!("LOCK TABLE {schema}.commands IN EXCLUSIVE MODE"))
+        .execute(&mut *tx)
+        .await?;
+    let plan = plan(recheck(&mut tx, rows).await?);
+    apply(&mut tx, &plan).await?;
+    tx.commit().await?;
+    Ok(plan)
+}
+
+pub async fn run(settings: &Settings) {
+    match renormalize(settings.dry_run).await {
+        Ok(p) => println!(
+            "{} {} of {} commands, {} merged into others",
+            if settings.dry_run { "would rekey" } else { "rekeyed" },
+            p.rekeyed,
+            p.scanned,
+            p.merged
+        ),
+        Err(e) => eprintln!("renormalize: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i32, stored: &str, key: &str, cnt: i32, when_run: i64) -> Row {
+        Row {
+            id,
+            stored: stored.into(),
+            key: key.into(),
+            cnt,
+            when_run,
+        }
+    }
+
+    #[test]
+    fn merges_into_latest() {
+        let mut p = plan(vec![
+            row(1, "ls -la", "ls -a -l", 3, 10),
+            row(2, "ls -a -l", "ls -a -l", 2, 20),
+            row(3, "ls -al", "ls -a -l", 1, 5),
+            row(4, "make", "make", 7, 1),
+        ]);
+        p.deletes.sort();
+        assert_eq!((p.scanned, p.rekeyed, p.merged), (4, 2, 2));
+        assert_eq!(p.deletes, [(1, 2), (3, 2)]);
+        assert_eq!(p.updates, [(2, "ls -a -l".to_string(), 6)]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{
+    conf::{self, RetentionConfig},
+    db,
+    settings::Settings,
+};
+use std::time::Duration;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(3600);
+
+struct Pruned {
+    aged: i64,
+    over: i64,
+}
+
+// rows older than max_age go first (unless run at least keep_count times),
+// then the oldest of what is left beyond max_rows.
+async fn prune(policy: &RetentionConfig, dry_run: bool) -> Result<Pruned, sqlx::Error> {
+    let pool = db::pg_pool();
+    let schema = &conf::conf_get().database.schema;
+    let cutoff = policy.max_age.map(|age| db::now_secs() - age.as_secs() as i64);
+    let aged_where = "when_run < $1 AND ($2::int IS NULL OR cnt < $2)";
+
+    let aged = match cutoff {
+        None => 0,
+        Some(_) if dry_run => {
+            sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {schema}.commands WHERE {aged_where}"
+            ))
+            .bind(cutoff)
+            .bind(policy.keep_count)
+            .fetch_one(pool)
+            .await?
+        }
+        Some(_) => sqlx::query(&format!("DELETE FROM {schema}.commands WHERE {aged_where}"))
+            .bind(cutoff)
+            .bind(policy.keep_count)
+            .execute(pool)
+            .await?
+            .rows_affected() as i64,
+    };
+
+    let over = match policy.max_rows {
+        None => 0,
+        Some(max) if dry_run => {
+            let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {schema}.commands"))
+                .fetch_one(pool)
+                .await?;
+            (total - aged - max).max(0)
+        }
+        Some(max) => sqlx::query(&format!(
+            "DELETE FROM {schema}.commands WHERE id IN \
+             (SELECT id FROM {schema}.commands ORDER BY when_run DESC OFFSET $1)"
+        ))
+        .bind(max)
+        .execute(pool)
+        .await?
+        .rows_affected() as i64,
+    };
+
+    Ok(Pruned { aged, over })
+}
+
+// the oldest `when_run` a new row can have without being pruned on the next
+// pass: past max_age, or older than everything kept once max_rows is reached.
+pub async fn floor() -> Result<Option<i64>, sqlx::Error> {
+    let Some(policy) = &conf::conf_get().retention else {
+        return Ok(None);
+    };
+    let schema = &conf::conf_get().database.schema;
+    let aged = policy.max_age.map(|age| db::now_secs() - age.as_secs() as i64);
+    let full = match policy.max_rows {
+        None => None,
+        Some(max) => {
+            sqlx::query_scalar(&format!(
+                "SELECT when_run FROM {schema}.commands ORDER BY when_run DESC OFFSET $1 LIMIT 1"
+            ))
+            .bind((max - 1).max(0))
+            .fetch_optional(db::pg_pool())
+            .await?
+        }
+    };
+    Ok(aged.max(full))
+}
+
+// cheap enough to run after every `add`: a single conditional update
+// decides whether this client is the one to prune in the current interval.
+pub async fn after_save() {
+    let Some(policy) = &conf::conf_get().retention else {
+        return;
+    };
+    let interval = policy.interval.unwrap_or(DEFAULT_INTERVAL).as_secs() as i64;
+    let now = db::now_secs();
+    let schema = &conf::conf_get().database.schema;
+    let claimed = sqlx::query(&format!(
+        "UPDATE {schema}.meta SET value = $1 WHERE key = 'pruned_at' AND value <= $2"
+    ))
+    .bind(now)
+    .bind(now - interval)
+    .execute(db::pg_pool())
+    .await
+    .map(|r| r.rows_affected() > 0)
+    .unwrap_or(false);
+
+    if claimed {
+        _ = prune(policy, false).await;
+    }
+}
+
+pub async fn run(settings: &Settings) {
+    let Some(policy) = &conf::conf_get().retention else {
+        eprintln!("no [retention] policy configured");
+        return;
+    };
+    match prune(policy, settings.dry_run).await {
+        Ok(p) if settings.dry_run => println!(
+            "would prune {} commands past max_age and {} beyond max_rows",
+            p.aged, p.over
+        ),
+        Ok(p) => println!(
+            "pruned {} commands past max_age and {} beyond max_rows",
+            p.aged, p.over
+        ),
+        Err(e) => eprintln!("prune: {e}"),
+    }
+}
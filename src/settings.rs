@@ -1,4 +1,4 @@
-use crate::cli::{Cli, SubCommand};
+use crate::cli::{Cli, ExportFormat, ImportFormat, ShellKind, SubCommand};
 use clap::Parser;
 
 #[derive(Debug)]
@@ -6,15 +6,44 @@ pub enum Mode {
     Add,
     Search,
     Init,
+    Import,
+    Export,
+    Backup,
+    Restore,
+    Prune,
+    List,
+    Renormalize,
+    SyncHistfile,
+    Forget,
 }
 
 pub struct Settings {
     pub mode: Mode,
     pub command: String,
     pub exit_code: i32,
+    pub shell: ShellKind,
+    pub duration_ms: Option<i64>,
+    pub known: Vec<String>,
     pub lightmode: bool,
     pub bottom: bool,
+    pub sync: bool,
     pub config_path: Option<String>,
+    pub output_fd: Option<i32>,
+    pub files: Vec<String>,
+    pub dry_run: bool,
+    pub import_from: ImportFormat,
+    pub export_format: ExportFormat,
+    pub output: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub exit_filter: Option<i32>,
+    pub archive: String,
+    pub merge: bool,
+    pub limit: Option<i64>,
+    pub offset: i64,
+    pub json: bool,
+    pub null_separated: bool,
+    pub digests: Vec<String>,
 }
 
 impl Default for Settings {
@@ -23,9 +52,29 @@ impl Default for Settings {
             mode: Mode::Add,
             command: String::new(),
             exit_code: 0,
+            shell: ShellKind::Bash,
+            duration_ms: None,
+            known: Vec::new(),
             lightmode: false,
             bottom: false,
+            sync: false,
             config_path: None,
+            output_fd: None,
+            files: Vec::new(),
+            dry_run: false,
+            import_from: ImportFormat::Bash,
+            export_format: ExportFormat::Jsonl,
+            output: None,
+            since: None,
+            until: None,
+            exit_filter: None,
+            archive: String::new(),
+            merge: false,
+            limit: None,
+            offset: 0,
+            json: false,
+            null_separated: false,
+            digests: Vec::new(),
         }
     }
 }
@@ -42,9 +91,17 @@ impl Settings {
             SubCommand::Add {
                 command,
                 exit,
+                shell,
+                duration,
+                known,
             } => {
                 settings.mode = Mode::Add;
                 settings.exit_code = exit;
+                settings.shell = shell;
+                settings.duration_ms = duration;
+                if let Some(known) = known {
+                    settings.known = known.split_whitespace().map(String::from).collect();
+                }
                 if !command.is_empty() {
                     settings.command = command.join(" ").trim().into();
                 }
@@ -54,8 +111,10 @@ impl Settings {
                 command,
                 bottom,
                 light,
+                fd,
             } => {
                 settings.mode = Mode::Search;
+                settings.output_fd = fd;
                 if !command.is_empty() {
                     settings.command = command.join(" ").trim().into();
                 }
@@ -63,10 +122,103 @@ impl Settings {
                 settings.lightmode = light;
             }
 
-            SubCommand::Init { bottom, light } => {
+            SubCommand::Init {
+                shell,
+                bottom,
+                light,
+                sync,
+            } => {
                 settings.mode = Mode::Init;
+                settings.shell = shell;
                 settings.bottom = bottom;
                 settings.lightmode = light;
+                settings.sync = sync;
+            }
+
+            SubCommand::Import {
+                files,
+                from,
+                dry_run,
+            } => {
+                settings.mode = Mode::Import;
+                settings.import_from = from;
+                settings.files = files;
+                settings.dry_run = dry_run;
+            }
+
+            SubCommand::Export {
+                pattern,
+                format,
+                output,
+                since,
+                until,
+                exit,
+            } => {
+                settings.mode = Mode::Export;
+                if !pattern.is_empty() {
+                    settings.command = pattern.join(" ").trim_start().into();
+                }
+                settings.export_format = format;
+                settings.output = output;
+                settings.since = since;
+                settings.until = until;
+                settings.exit_filter = exit;
+            }
+
+            SubCommand::Backup { file } => {
+                settings.mode = Mode::Backup;
+                settings.archive = file;
+            }
+
+            SubCommand::Restore { file, merge } => {
+                settings.mode = Mode::Restore;
+                settings.archive = file;
+                settings.merge = merge;
+            }
+
+            SubCommand::Prune { dry_run } => {
+                settings.mode = Mode::Prune;
+                settings.dry_run = dry_run;
+            }
+
+            SubCommand::List {
+                pattern,
+                limit,
+                offset,
+                json,
+                null,
+            } => {
+                settings.mode = Mode::List;
+                if !pattern.is_empty() {
+                    settings.command = pattern.join(" ").trim_start().into();
+                }
+                settings.limit = limit;
+                settings.offset = offset;
+                settings.json = json;
+                settings.null_separated = null;
+            }
+
+            SubCommand::Renormalize { dry_run } => {
+                settings.mode = Mode::Renormalize;
+                settings.dry_run = dry_run;
+            }
+
+            SubCommand::SyncHistfile {
+                limit,
+                dry_run,
+                known,
+            } => {
+                settings.mode = Mode::SyncHistfile;
+                settings.limit = limit;
+                settings.dry_run = dry_run;
+                if let Some(known) = known {
+                    settings.known = known.split_whitespace().map(String::from).collect();
+                }
+            }
+
+            SubCommand::Forget { digests } => {
+                settings.mode = Mode::Forget;
+                settings.digests = digests;
             }
         }
 
@@ -1,3 +1,4 @@
+use crate::{cli::ShellKind, conf};
 use is_executable::IsExecutable;
 use relative_path::RelativePath;
 use std::path::Path;
@@ -8,32 +9,381 @@ trait Shell {
 }
 
 pub(super) mod bash {
+    type State = &'static dyn Fn(&mut Bash, u8);
+
+    // name, short options that take a value, positionals before the command
+    const WRAPPERS: [(&str, &str, usize); 14] = [
+        ("sudo", "CDghprtTUu", 0),
+        ("doas", "Cu", 0),
+        ("env", "CSu", 0),
+        ("nohup", "", 0),
+        ("exec", "a", 0),
+        ("command", "", 0),
+        ("builtin", "", 0),
+        ("time", "fo", 0),
+        ("nice", "n", 0),
+        ("ionice", "cnp", 0),
+        ("stdbuf", "eio", 0),
+        ("timeout", "ks", 1),
+        ("chrt", "", 1),
+        ("taskset", "", 1),
+    ];
+
     pub struct Bash {
-        fns: Vec<&'static dyn Fn(&mut Self, u8)>,
+        fns: Vec<State>,
         cmd: Vec<u8>,
+        wrappers: &'static [String],
+        // the current word has begun, even if only with an empty ''
+        started: bool,
+        // offset of the first unquoted '=', with nothing quoted before it
+        eq: Option<usize>,
+        quoted: bool,
+        // the word expands ($var, $(..), globs), its value is unknown here
+        expands: bool,
+        // an unquoted ; | & ends the simple command
+        ended: bool,
+        // pending \xHH or \NNN value in $'...' and its digit count
+        code: Option<(u8, u8)>,
+    }
+
+    struct Word {
+        text: String,
+        assignment: bool,
+        expands: bool,
     }
 
     impl Bash {
-        pub fn new() -> Self {
+        pub fn new(wrappers: &'static [String]) -> Self {
             Bash {
                 fns: vec![&Self::bare],
                 cmd: Vec::new(),
+                wrappers,
+                started: false,
+                eq: None,
+                quoted: false,
+                expands: false,
+                ended: false,
+                code: None,
             }
         }
 
-        fn cmd_array(&mut self) -> &mut Vec<u8> { &mut self.cmd }
+        fn cmd_array(&mut self) -> &mut Vec<u8> {
+            self.started = true;
+            &mut self.cmd
+        }
+
+        fn open(&mut self, state: State) {
+            self.started = true;
+            self.quoted = true;
+            self.fns.push(state);
+        }
 
         fn bare(&mut self, byte: u8) {
-            if byte == b' ' || byte == b'\t' {
+            match byte {
+                b' ' | b'\t' | b'\n' if !self.started => {}
+                b' ' | b'\t' | b'\n' => {
+                    self.fns.pop();
+                }
+                b';' | b'|' | b'&' => {
+                    self.ended = true;
+                    self.fns.pop();
+                }
+                b'\'' => self.open(&Self::single),
+                b'"' => self.open(&Self::double),
+                b'\\' => self.open(&Self::escape),
+                b'$' => self.open(&Self::dollar),
+                b'=' if self.eq.is_none() && !self.quoted => {
+                    self.eq = Some(self.cmd.len());
+                    self.cmd_array().push(byte);
+                }
+                b'*' | b'?' | b'[' | b'`' => {
+                    self.expands = true;
+                    self.cmd_array().push(byte);
+                }
+                _ => self.cmd_array().push(byte),
+            }
+        }
+
+        // no escapes at all inside '...'
+        fn single(&mut self, byte: u8) {
+            if byte == b'\'' {
                 self.fns.pop();
-            } else if byte == b'\'' {
-                self.fns.push(&Self::quote::<b'\''>);
-            } else if byte == b'"' {
-                self.fns.push(&Self::quote::<b'"'>);
-            } else if byte == b'\\' {
-                self.fns.push(&Self::escape);
             } else {
-                self.cmd_array().push(byte);
+                self.cmd.push(byte);
+            }
+        }
+
+        fn double(&mut self, byte: u8) {
+            match byte {
+                b'"' => {
+                    self.fns.pop();
+                }
+                b'\\' => self.fns.push(&Self::double_escape),
+                b'$' | b'`' => {
+                    self.expands = true;
+                    self.cmd.push(byte);
+                }
+                _ => self.cmd.push(byte),
+            }
+        }
+
+        fn double_escape(&mut self, byte: u8) {
+            self.fns.pop();
+            match byte {
+                b'$' | b'`' | b'"' | b'\\' => self.cmd.push(byte),
+                b'\n' => {}
+                _ => {
+                    self.cmd.push(b'\\');
+                    self.cmd.push(byte);
+                }
+            }
+        }
+
+        fn escape(&mut self, byte: u8) {
+            self.fns.pop();
+            if byte != b'\n' {
+                self.cmd.push(byte);
+            }
+        }
+
+        fn dollar(&mut self, byte: u8) {
+            self.fns.pop();
+            match byte {
+                b'\'' => self.fns.push(&Self::ansi),
+                b'"' => self.fns.push(&Self::double),
+                _ => {
+                    self.expands = true;
+                    self.cmd.push(b'$');
+                    self.bare(byte);
+                }
+            }
+        }
+
+        fn ansi(&mut self, byte: u8) {
+            match byte {
+                b'\'' => {
+                    self.fns.pop();
+                }
+                b'\\' => self.fns.push(&Self::ansi_escape),
+                _ => self.cmd.push(byte),
+            }
+        }
+
+        fn ansi_escape(&mut self, byte: u8) {
+            self.fns.pop();
+            let c = match byte {
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b'e' | b'E' => 0x1b,
+                b'f' => 0x0c,
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'v' => 0x0b,
+                b'x' => {
+                    self.code = Some((0, 0));
+                    self.fns.push(&Self::ansi_code::<16, 2>);
+                    return;
+                }
+                b'0'..=b'7' => {
+                    self.code = Some((byte - b'0', 1));
+                    self.fns.push(&Self::ansi_code::<8, 3>);
+                    return;
+                }
+                b'\\' | b'\'' | b'"' | b'?' => byte,
+                _ => {
+                    self.cmd.push(b'\\');
+                    byte
+                }
+            };
+            self.cmd.push(c);
+        }
+
+        fn ansi_code<const RADIX: u32, const N: u8>(&mut self, byte: u8) {
+            let (v, n) = self.code.unwrap_or_default();
+            if let (Some(d), true) = ((byte as char).to_digit(RADIX), n < N) {
+                self.code = Some((v.wrapping_mul(RADIX as u8).wrapping_add(d as u8), n + 1));
+                return;
+            }
+            self.flush();
+            self.ansi(byte);
+        }
+
+        fn flush(&mut self) {
+            if let Some((v, n)) = self.code.take() {
+                self.fns.pop();
+                if n > 0 {
+                    self.cmd.push(v);
+                }
+            }
+        }
+
+        fn next_word<I: Iterator<Item = u8>>(&mut self, bytes: &mut I) -> Option<Word> {
+            if self.ended {
+                return None;
+            }
+            self.fns = vec![&Self::bare];
+            self.started = false;
+            self.eq = None;
+            self.quoted = false;
+            self.expands = false;
+
+            for b in bytes.by_ref() {
+                let f = self.fns[self.fns.len() - 1];
+                f(self, b);
+                if self.fns.is_empty() {
+                    break;
+                }
+            }
+            // input ran out before the word did
+            self.flush();
+            if !self.fns.is_empty() {
+                self.ended = true;
+            }
+            if !self.started {
+                return None;
+            }
+
+            let cmd = std::mem::take(&mut self.cmd);
+            let assignment = self.eq.is_some_and(|eq| is_name(&cmd[..eq]));
+            Some(Word {
+                text: String::from_utf8_lossy(&cmd).into_owned(),
+                assignment,
+                expands: self.expands,
+            })
+        }
+
+        fn wrapper(&self, word: &str) -> Option<(&'static str, usize)> {
+            if let Some(&(_, valued, n)) = WRAPPERS.iter().find(|w| w.0 == word) {
+                return Some((valued, n));
+            }
+            self.wrappers.iter().any(|w| w == word).then_some(("", 0))
+        }
+    }
+
+    fn is_name(s: &[u8]) -> bool {
+        matches!(s.first(), Some(c) if c.is_ascii_alphabetic() || *c == b'_')
+            && s.iter().all(|c| c.is_ascii_alphanumeric() || *c == b'_')
+    }
+
+    impl super::Shell for Bash {
+        const NOT_FOUND: i32 = 127;
+
+        // skips `NAME=value` prefixes and wrappers such as `sudo -u x`,
+        // `env A=1` or `timeout 5`, and returns the word that is run
+        fn arg0_from_cmdline(&mut self, line: &str) -> String {
+            let mut bytes = line.bytes();
+            let mut valued: Option<&str> = None;
+            let mut options = true;
+            let mut positionals = 0;
+            let mut value_next = false;
+
+            while let Some(word) = self.next_word(&mut bytes) {
+                if word.assignment {
+                    continue;
+                }
+                if let Some(opts) = valued {
+                    let text = word.text.as_str();
+                    if value_next {
+                        value_next = false;
+                        continue;
+                    }
+                    if options && text == "--" {
+                        options = false;
+                        continue;
+                    }
+                    if options && text.len() > 1 && text.starts_with('-') {
+                        // `-u root`: the value follows only when the option ends the cluster
+                        value_next = !text.starts_with("--")
+                            && text[1..].find(|c: char| opts.contains(c)) == Some(text.len() - 2);
+                        continue;
+                    }
+                    if positionals > 0 {
+                        positionals -= 1;
+                        continue;
+                    }
+                }
+                if let Some((opts, n)) = self.wrapper(&word.text) {
+                    valued = Some(opts);
+                    options = true;
+                    positionals = n;
+                    continue;
+                }
+                if word.expands {
+                    return String::new();
+                }
+                return word.text;
+            }
+            String::new()
+        }
+    }
+}
+
+pub(super) mod zsh {
+    use super::{bash::Bash, Shell};
+
+    // quoting is close enough to bash, zsh adds `=cmd` (the path of cmd),
+    // `=(...)` substitution and glob qualifiers on top.
+    pub struct Zsh {
+        bash: Bash,
+    }
+
+    impl Zsh {
+        pub fn new(wrappers: &'static [String]) -> Self {
+            Zsh {
+                bash: Bash::new(wrappers),
+            }
+        }
+    }
+
+    impl Shell for Zsh {
+        const NOT_FOUND: i32 = 127;
+
+        fn arg0_from_cmdline(&mut self, line: &str) -> String {
+            let arg0 = self.bash.arg0_from_cmdline(line);
+            if arg0.contains(['*', '?', '[', '(']) {
+                return String::new();
+            }
+            match arg0.strip_prefix('=') {
+                Some(name) => name.to_string(),
+                None => arg0,
+            }
+        }
+    }
+}
+
+pub(super) mod fish {
+    type State = &'static dyn Fn(&mut Fish, u8);
+
+    // no $'...' in fish, and single quotes do take `\'` and `\\` escapes
+    pub struct Fish {
+        fns: Vec<State>,
+        cmd: Vec<u8>,
+        expands: bool,
+    }
+
+    impl Fish {
+        pub fn new() -> Self {
+            Fish {
+                fns: vec![&Self::bare],
+                cmd: Vec::new(),
+                expands: false,
+            }
+        }
+
+        fn bare(&mut self, byte: u8) {
+            match byte {
+                b' ' | b'\t' => {
+                    self.fns.pop();
+                }
+                b'\'' => self.fns.push(&Self::quote::<b'\''>),
+                b'"' => self.fns.push(&Self::quote::<b'"'>),
+                b'\\' => self.fns.push(&Self::escape),
+                b'*' | b'?' | b'{' | b'$' | b'(' => {
+                    self.expands = true;
+                    self.cmd.push(byte);
+                }
+                _ => self.cmd.push(byte),
             }
         }
 
@@ -41,60 +391,103 @@ pub(super) mod bash {
             if byte == I {
                 self.fns.pop();
             } else if byte == b'\\' {
-                self.fns.push(&Self::escape);
+                self.fns.push(&Self::quoted_escape::<I>);
             } else {
-                self.cmd_array().push(byte);
+                if I == b'"' && byte == b'$' {
+                    self.expands = true;
+                }
+                self.cmd.push(byte);
             }
         }
 
-        fn escape(&mut self, byte: u8) {
-            let n = self.fns.len();
-            let v = self.cmd_array();
-            if n == 2 {
-                match byte {
-                    b'n' => v.push(b'\n'),
-                    b't' => v.push(b'\t'),
-                    b'r' => v.push(b'\r'),
-                    _ => v.push(byte),
+        fn quoted_escape<const I: u8>(&mut self, byte: u8) {
+            self.fns.pop();
+            match byte {
+                b'\\' => self.cmd.push(byte),
+                _ if byte == I => self.cmd.push(byte),
+                b'$' if I == b'"' => self.cmd.push(byte),
+                b'\n' if I == b'"' => {}
+                _ => {
+                    self.cmd.push(b'\\');
+                    self.cmd.push(byte);
                 }
-            } else {
-                v.push(b'\\');
-                v.push(byte);
             }
+        }
+
+        fn escape(&mut self, byte: u8) {
             self.fns.pop();
+            match byte {
+                b'n' => self.cmd.push(b'\n'),
+                b't' => self.cmd.push(b'\t'),
+                b'r' => self.cmd.push(b'\r'),
+                b'e' => self.cmd.push(0x1b),
+                b'\n' => {}
+                _ => self.cmd.push(byte),
+            }
         }
     }
 
-    impl super::Shell for Bash {
+    impl super::Shell for Fish {
         const NOT_FOUND: i32 = 127;
 
         fn arg0_from_cmdline(&mut self, line: &str) -> String {
-            let bytes = line.as_bytes();
-            let nb = bytes.len();
-            self.cmd.reserve(nb);
-
-            let mut nr = self.fns.len();
-            for &b in bytes {
-                self.fns[nr - 1](self, b);
-                nr = self.fns.len();
-                if nr == 0 {
+            for &b in line.as_bytes() {
+                let f = self.fns[self.fns.len() - 1];
+                f(self, b);
+                if self.fns.is_empty() {
                     break;
                 }
             }
-            let result = String::from_utf8(std::mem::take(&mut self.cmd)).unwrap();
-            self.cmd.reserve(128);
+            let result = String::from_utf8(std::mem::take(&mut self.cmd)).unwrap_or_default();
+            if self.expands {
+                return String::new();
+            }
             result
         }
     }
 }
 
-pub(super) fn execute_able(cmd: &str, exit_code: i32) -> bool {
-    if exit_code != bash::Bash::NOT_FOUND {
+fn wrappers() -> &'static [String] {
+    conf::conf_try_get()
+        .and_then(|c| c.shell.as_ref())
+        .map_or(&[][..], |s| &s.wrappers[..])
+}
+
+pub(super) fn arg0(cmd: &str, shell: ShellKind) -> String {
+    match shell {
+        ShellKind::Bash => bash::Bash::new(wrappers()).arg0_from_cmdline(cmd),
+        ShellKind::Zsh => zsh::Zsh::new(wrappers()).arg0_from_cmdline(cmd),
+        ShellKind::Fish => fish::Fish::new().arg0_from_cmdline(cmd),
+    }
+}
+
+// `known` holds the aliases, functions and builtins of the calling shell
+pub(super) fn execute_able(cmd: &str, exit_code: i32, shell: ShellKind, known: &[String]) -> bool {
+    match shell {
+        ShellKind::Bash => resolves(bash::Bash::new(wrappers()), cmd, exit_code, known),
+        ShellKind::Zsh => resolves(zsh::Zsh::new(wrappers()), cmd, exit_code, known),
+        ShellKind::Fish => resolves(fish::Fish::new(), cmd, exit_code, known),
+    }
+}
+
+// for a command found in a history file rather than just run: a relative
+// path depends on a cwd that is not known any more, so only a bare name
+// or an absolute path can be told not to exist
+pub(super) fn found_later(cmd: &str, shell: ShellKind, known: &[String]) -> bool {
+    let name = arg0(cmd, shell);
+    (name.contains('/') && !name.starts_with(['/', '~'])) || execute_able(cmd, 127, shell, known)
+}
+
+// an empty arg0 means the shell can't tell statically, keep the command
+fn resolves<S: Shell>(mut shell: S, cmd: &str, exit_code: i32, known: &[String]) -> bool {
+    if exit_code != S::NOT_FOUND {
         return true;
     }
 
-    let mut shell = bash::Bash::new();
     let cmd = shell.arg0_from_cmdline(cmd);
+    if cmd.is_empty() || known.contains(&cmd) {
+        return true;
+    }
     let cmd = cmd.as_str();
     let cmd = shellexpand::tilde(cmd);
     let cmd = cmd.as_ref();
@@ -117,3 +510,93 @@ pub(super) fn execute_able(cmd: &str, exit_code: i32) -> bool {
         Some(ref pathbuf) => pathbuf.as_path().is_executable(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bash_arg0() {
+        let cases = [
+            ("ls -la", "ls"),
+            ("  ls", "ls"),
+            ("'my cmd' x", "my cmd"),
+            (r"'a\b'", r"a\b"),
+            (r#""a \"b\" \c""#, r#"a "b" \c"#),
+            (r"my\ cmd", "my cmd"),
+            (r"\ls", "ls"),
+            (r"$'a\tb' x", "a\tb"),
+            (r"$'\x41\102\'' x", "AB'"),
+            (r"$'\x4'", "\x04"),
+            ("FOO=1 make -j8", "make"),
+            ("FOO=1 BAR='a b' make", "make"),
+            ("'FOO'=1", "FOO=1"),
+            ("./a=b.sh", "./a=b.sh"),
+            ("sudo kubectl get pods", "kubectl"),
+            ("sudo -u root -E kubectl", "kubectl"),
+            ("sudo -uroot kubectl", "kubectl"),
+            ("sudo -- kubectl", "kubectl"),
+            ("time cargo build", "cargo"),
+            ("time -p cargo build", "cargo"),
+            ("env X=1 Y=2 foo", "foo"),
+            ("env -u HOME foo", "foo"),
+            ("nohup ./server &", "./server"),
+            ("exec -a name zsh", "zsh"),
+            ("command ls", "ls"),
+            ("builtin echo", "echo"),
+            ("timeout 5 curl x", "curl"),
+            ("nice -n 10 make", "make"),
+            ("sudo env A=1 nohup foo", "foo"),
+            ("ls;foo", "ls"),
+            ("ls|grep x", "ls"),
+            ("FOO=1", ""),
+            ("sudo", ""),
+            ("$EDITOR file", ""),
+            ("\"$HOME/bin/x\"", ""),
+            ("$(which ls)", ""),
+            ("./run*.sh", ""),
+            ("", ""),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(bash::Bash::new(&[]).arg0_from_cmdline(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn history_names() {
+        let known = ["ll".to_string()];
+        assert!(found_later("ll -a", ShellKind::Bash, &known));
+        assert!(found_later("ls -la", ShellKind::Bash, &known));
+        assert!(found_later("./build.sh --release", ShellKind::Bash, &known));
+        assert!(!found_later("gti status", ShellKind::Bash, &known));
+        assert!(!found_later("/no/such/tool x", ShellKind::Bash, &known));
+    }
+
+    #[test]
+    fn configured_wrappers() {
+        let extra = Box::leak(vec!["proxychains4".to_string()].into_boxed_slice());
+        assert_eq!(bash::Bash::new(extra).arg0_from_cmdline("proxychains4 -q curl x"), "curl");
+        assert_eq!(bash::Bash::new(&[]).arg0_from_cmdline("proxychains4 -q curl x"), "proxychains4");
+    }
+
+    #[test]
+    fn zsh_arg0() {
+        let arg0 = |line| zsh::Zsh::new(&[]).arg0_from_cmdline(line);
+        assert_eq!(arg0("=ls -la"), "ls");
+        assert_eq!(arg0("'my cmd' x"), "my cmd");
+        assert_eq!(arg0("./build*(x) arg"), "");
+        assert_eq!(arg0("=(print hi)"), "");
+    }
+
+    #[test]
+    fn fish_arg0() {
+        let arg0 = |line| fish::Fish::new().arg0_from_cmdline(line);
+        assert_eq!(arg0("ls -la"), "ls");
+        assert_eq!(arg0(r"'it\'s' x"), "it's");
+        assert_eq!(arg0(r"'a\b'"), r"a\b");
+        assert_eq!(arg0(r#""my \"cmd\"" x"#), "my \"cmd\"");
+        assert_eq!(arg0(r"my\ cmd x"), "my cmd");
+        assert_eq!(arg0("$EDITOR file"), "");
+        assert_eq!(arg0("(which ls) -la"), "");
+    }
+}
@@ -1,59 +1,375 @@
+use crate::db;
 use std::{
     env, fs,
-    fs::File,
+    fs::{File, OpenOptions},
     io,
-    io::Read,
+    io::{Read, Seek, Write},
+    ops::Range,
+    os::unix::{
+        fs::{MetadataExt, OpenOptionsExt, PermissionsExt},
+        io::AsRawFd,
+    },
     path::{Path, PathBuf},
 };
 
-fn read_history_file(path: &Path) -> Result<String, io::Error> {
+fn read_history_file(path: &Path) -> Result<Vec<u8>, io::Error> {
     let mut f = File::open(path)?;
     let mut buffer = Vec::new();
     f.read_to_end(&mut buffer)?;
-    Ok(String::from_utf8_lossy(&buffer).to_string())
+    Ok(buffer)
 }
 
-fn has_leading_timestamp(line: &str) -> bool {
-    line.len() == 11 && line.starts_with('#') && line[1..].chars().all(|c| c.is_ascii_digit())
+fn has_leading_timestamp(line: &[u8]) -> bool {
+    line.len() == 11 && line[0] == b'#' && line[1..].iter().all(u8::is_ascii_digit)
 }
 
-fn history_file_path() -> Option<PathBuf> {
-    let path = PathBuf::from(env::var("HISTFILE").unwrap_or_default());
-    fs::canonicalize(path).ok()
+pub struct Entry {
+    pub when: Option<i64>,
+    pub command: String,
 }
 
-fn load_history(path: &Path) -> Option<Vec<String>> {
-    let contents = read_history_file(path).ok()?;
-    Some(
-        contents
-            .split('\n')
-            .filter(|line| !has_leading_timestamp(line) && !line.is_empty())
-            .map(|cmd| cmd.trim().to_string())
-            .collect(),
-    )
+// an entry together with the bytes it occupies in the file, its `#<epoch>`
+// line and trailing newline included, so it can be cut out verbatim.
+struct Span {
+    range: Range<usize>,
+    entry: Entry,
+}
+
+// once a file carries `#<epoch>` lines, every line up to the next stamp
+// belongs to the same (multi-line) entry; unstamped files are one per line.
+fn entry_spans(contents: &[u8]) -> Vec<Span> {
+    let mut spans: Vec<Span> = vec![];
+    let mut stamped = false;
+    let mut offset = 0;
+    for raw in contents.split_inclusive(|&b| b == b'\n') {
+        let range = offset..offset + raw.len();
+        offset = range.end;
+        let line = raw.strip_suffix(b"\n").unwrap_or(raw);
+
+        if has_leading_timestamp(line) {
+            stamped = true;
+            spans.push(Span {
+                range,
+                entry: Entry {
+                    when: std::str::from_utf8(&line[1..])
+                        .ok()
+                        .and_then(|t| t.parse().ok()),
+                    command: String::new(),
+                },
+            });
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(line);
+        match spans.last_mut() {
+            Some(last) if stamped => {
+                last.range.end = range.end;
+                if !last.entry.command.is_empty() || !text.is_empty() {
+                    if !last.entry.command.is_empty() {
+                        last.entry.command.push('\n');
+                    }
+                    last.entry.command.push_str(&text);
+                }
+            }
+            _ if text.is_empty() => {}
+            _ => spans.push(Span {
+                range,
+                entry: Entry {
+                    when: None,
+                    command: text.into_owned(),
+                },
+            }),
+        }
+    }
+    for span in &mut spans {
+        let len = span.entry.command.trim_end_matches('\n').len();
+        span.entry.command.truncate(len);
+    }
+    spans
+}
+
+pub fn parse_entries(contents: &str) -> Vec<Entry> {
+    entry_spans(contents.as_bytes())
+        .into_iter()
+        .map(|s| s.entry)
+        .filter(|e| !e.command.is_empty())
+        .collect()
+}
+
+pub fn history_file_path() -> Option<PathBuf> {
+    let path = PathBuf::from(env::var("HISTFILE").unwrap_or_default());
+    fs::canonicalize(path).ok()
 }
 
 pub fn full_history() -> Vec<String> {
     let Some(path) = history_file_path() else {
         return vec![];
     };
-    load_history(&path).unwrap_or_default()
+    let Ok(contents) = read_history_file(&path) else {
+        return vec![];
+    };
+    parse_entries(&String::from_utf8_lossy(&contents))
+        .into_iter()
+        .map(|e| e.command)
+        .collect()
+}
+
+// drops the entries that would be stored as `command`, every other byte
+// of the file is written back untouched.
+fn without(contents: &[u8], command: &str) -> Option<Vec<u8>> {
+    let target = db::sanitize(command);
+    let mut out = Vec::with_capacity(contents.len());
+    let mut kept = 0;
+    for span in entry_spans(contents) {
+        if span.entry.command.is_empty()
+            || db::sanitize(&span.entry.command).trim_start() != target.trim_start()
+        {
+            continue;
+        }
+        out.extend_from_slice(&contents[kept..span.range.start]);
+        kept = span.range.end;
+    }
+    if kept == 0 {
+        return None;
+    }
+    out.extend_from_slice(&contents[kept..]);
+    Some(out)
+}
+
+// bash appends to HISTFILE without taking any lock, so this only orders
+// rewrites by rhis itself; appends racing with a rewrite are merged below.
+fn lock(path: &Path) -> io::Result<File> {
+    loop {
+        let file = File::open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // the previous holder may have renamed a new file over the locked one
+        if still_at(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
+
+fn still_at(file: &File, path: &Path) -> io::Result<bool> {
+    let (held, now) = (file.metadata()?, fs::metadata(path)?);
+    Ok(held.dev() == now.dev() && held.ino() == now.ino())
+}
+
+// leaves the file positioned right after `prefix` when it still starts with it
+fn starts_with(file: &mut File, prefix: &[u8]) -> io::Result<bool> {
+    let mut head = Vec::with_capacity(prefix.len());
+    file.rewind()?;
+    file.take(prefix.len() as u64).read_to_end(&mut head)?;
+    Ok(head == prefix)
+}
+
+// writes `out` plus whatever got appended to `file` after `read` was taken
+// from it, then renames it into place. false means the file was rewritten
+// or replaced meanwhile and the edit has to be redone against the new one.
+fn replace(
+    file: &mut File,
+    path: &Path,
+    tmp_path: &Path,
+    read: &[u8],
+    out: &[u8],
+) -> io::Result<bool> {
+    let mode = file.metadata()?.permissions().mode();
+    let mut tmp = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(tmp_path)?;
+    tmp.write_all(out)?;
+    tmp.sync_all()?;
+
+    // growth only counts as an append while everything seen so far is
+    // still there, byte for byte, at the start of the same file
+    let mut seen = read.to_vec();
+    loop {
+        if !still_at(file, path)? || !starts_with(file, &seen)? {
+            fs::remove_file(tmp_path)?;
+            return Ok(false);
+        }
+        let mut tail = vec![];
+        file.read_to_end(&mut tail)?;
+        if tail.is_empty() {
+            break;
+        }
+        tmp.write_all(&tail)?;
+        tmp.sync_all()?;
+        seen.extend_from_slice(&tail);
+    }
+
+    fs::rename(tmp_path, path)?;
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(true)
 }
 
-pub fn delete_lines(command: &str) {
+pub(crate) fn rewrite(path: &Path, edit: impl Fn(&[u8]) -> Option<Vec<u8>>) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{name}.rhis-{}", std::process::id()));
+
+    loop {
+        let mut file = lock(path)?;
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        let Some(out) = edit(&contents) else {
+            return Ok(());
+        };
+        match replace(&mut file, path, &tmp_path, &contents, &out) {
+            Ok(true) => return Ok(()),
+            Ok(false) => continue,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        }
+    }
+}
+
+// appends `#<epoch>`-stamped entries, as bash writes them with HISTTIMEFORMAT set
+pub fn append_entries(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    rewrite(path, |contents| {
+        let mut out = contents.to_vec();
+        if !out.is_empty() && !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+        for e in entries {
+            out.extend_from_slice(format!("#{}\n{}\n", e.when.unwrap_or(0), e.command).as_bytes());
+        }
+        Some(out)
+    })
+}
+
+pub fn delete_lines(commands: &[&str]) {
     let Some(path) = history_file_path() else {
         return;
     };
-    let Some(commands) = load_history(&path) else {
-        return;
-    };
-    if commands.is_empty() {
-        return;
+    // called from inside the search screen, where there is nowhere to report
+    _ = rewrite(&path, |contents| {
+        let mut out: Option<Vec<u8>> = None;
+        for command in commands {
+            if let Some(rest) = without(out.as_deref().unwrap_or(contents), command) {
+                out = Some(rest);
+            }
+        }
+        out
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unstamped_lines() {
+        let entries = parse_entries("ls -la\ncd /tmp\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].when, None);
+        assert_eq!(entries[1].command, "cd /tmp");
+    }
+
+    #[test]
+    fn stamped_multiline() {
+        let entries =
+            parse_entries("echo a\n#1700000000\nfor i in 1 2\ndo echo $i\ndone\n#1700000005\nls\n");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].when, None);
+        assert_eq!(entries[1].when, Some(1700000000));
+        assert_eq!(entries[1].command, "for i in 1 2\ndo echo $i\ndone");
+        assert_eq!(entries[2].when, Some(1700000005));
+        assert_eq!(entries[2].command, "ls");
+    }
+
+    #[test]
+    fn delete_keeps_stamps() {
+        let file =
+            b"#1700000000\nls\n#1700000001\nfor i in 1 2\ndo echo $i\ndone\n#1700000002\ncd /tmp\n";
+        let rest = without(file, "for i in 1 2\ndo echo $i\ndone").unwrap();
+        assert_eq!(rest, b"#1700000000\nls\n#1700000002\ncd /tmp\n");
+        assert!(without(file, "pwd").is_none());
+
+        let rest = without(b"ls\n\ncd /tmp\nls", "ls").unwrap();
+        assert_eq!(rest, b"\ncd /tmp\n");
+    }
+
+    #[test]
+    fn rewrite_in_place() {
+        let path = env::temp_dir().join(format!("rhis-histfile-{}", std::process::id()));
+        fs::write(&path, "#1700000000\nls\n#1700000001\npwd\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        rewrite(&path, |contents| without(contents, "ls")).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"#1700000001\npwd\n");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        let tmp = path.with_file_name(format!(".rhis-histfile-{0}.rhis-{0}", std::process::id()));
+        assert!(!tmp.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    // the edit runs while another shell writes to the file: its first call
+    // does `write` to `path` before returning, later calls do not.
+    fn concurrent(name: &str, write: impl Fn(&Path), expect: &[u8]) {
+        let path = env::temp_dir().join(format!("rhis-{name}-{}", std::process::id()));
+        fs::write(&path, "#1700000000\nls\n#1700000001\npwd\n").unwrap();
+        let calls = std::cell::Cell::new(0);
+        rewrite(&path, |contents| {
+            if calls.replace(calls.get() + 1) == 0 {
+                write(&path);
+            }
+            without(contents, "ls")
+        })
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), expect);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rewrite_keeps_appended() {
+        let append = |path: &Path| {
+            let mut f = OpenOptions::new().append(true).open(path).unwrap();
+            f.write_all(b"#1700000002\ncd /tmp\n").unwrap();
+        };
+        let expect = b"#1700000001\npwd\n#1700000002\ncd /tmp\n";
+        concurrent("appended", append, expect);
+    }
+
+    #[test]
+    fn rewrite_redone_after_truncate() {
+        let truncate = |path: &Path| {
+            let mut f = OpenOptions::new().write(true).open(path).unwrap();
+            f.set_len(0).unwrap();
+            f.write_all(b"#1700000003\nls\n#1700000004\ndf\n").unwrap();
+        };
+        concurrent("truncated", truncate, b"#1700000004\ndf\n");
+    }
+
+    #[test]
+    fn rewrite_redone_after_regrow() {
+        let regrow = |path: &Path| {
+            let mut f = OpenOptions::new().write(true).open(path).unwrap();
+            f.set_len(0).unwrap();
+            f.write_all(b"#1700000003\nls\n#1700000004\ndf -h /var\n#1700000005\necho hi\n")
+                .unwrap();
+        };
+        let expect = b"#1700000004\ndf -h /var\n#1700000005\necho hi\n";
+        concurrent("regrown", regrow, expect);
+    }
+
+    #[test]
+    fn rewrite_redone_after_rename() {
+        let rename = |path: &Path| {
+            let other = path.with_extension("new");
+            fs::write(&other, "#1700000003\nls\n#1700000004\nuptime\n").unwrap();
+            fs::rename(&other, path).unwrap();
+        };
+        concurrent("renamed", rename, b"#1700000004\nuptime\n");
     }
-    let lines: Vec<String> = commands
-        .into_iter()
-        .filter(|cmd| command != cmd)
-        .chain(Some(String::new()))
-        .collect();
-    let _ = fs::write(&path, lines.join("\n"));
 }
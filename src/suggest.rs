@@ -0,0 +1,100 @@
+use crate::{cli::ShellKind, db, normalize, shell};
+
+const CANDIDATES: i64 = 2000;
+const SHOWN: usize = 3;
+
+// optimal string alignment: swapping two neighbours (`gti`) is one edit
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let prev = &rows[i - 1];
+            row[j] = (prev[j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                .min(prev[j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+// typos worth correcting are a couple of edits away, relative to length
+fn close(arg0: &str, d: usize) -> bool {
+    match d {
+        1 => arg0.chars().count() >= 2,
+        2 => arg0.chars().count() >= 6,
+        _ => false,
+    }
+}
+
+// ranks stored commands whose arg0 is a near miss of the mistyped one,
+// then by how close the whole normalized line is.
+pub fn rank(command: &str, candidates: Vec<(String, i32)>, shell: ShellKind) -> Vec<String> {
+    let arg0 = shell::arg0(command, shell);
+    if arg0.is_empty() {
+        return vec![];
+    }
+    let normalized = normalize::normalize(command);
+
+    let mut scored: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|(cmd, cnt)| {
+            let d = distance(&arg0, &shell::arg0(&cmd, shell));
+            if !close(&arg0, d) {
+                return None;
+            }
+            let full = distance(&normalized, &normalize::normalize(&cmd));
+            Some(((d, full, -cnt), cmd))
+        })
+        .collect();
+    scored.sort();
+    scored.into_iter().take(SHOWN).map(|(_, cmd)| cmd).collect()
+}
+
+pub async fn did_you_mean(command: &str, shell: ShellKind) {
+    let candidates = db::frequent_commands(CANDIDATES).await;
+    let found = rank(command, candidates, shell);
+    if !found.is_empty() {
+        eprintln!("rhis: did you mean: {}", found.join("  |  "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(cmds: &[(&str, i32)]) -> Vec<(String, i32)> {
+        cmds.iter().map(|&(c, n)| (c.to_string(), n)).collect()
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(distance("kubeclt", "kubectl"), 1);
+        assert_eq!(distance("ca", "abc"), 3);
+        assert_eq!(distance("", "ls"), 2);
+        assert_eq!(distance("cargo", "cargo"), 0);
+    }
+
+    #[test]
+    fn ranks_transpositions() {
+        let cmds = stored(&[
+            ("git status", 5),
+            ("make", 3),
+            ("ls -l", 9),
+            ("kubectl get pods", 1),
+            ("gh pr list", 2),
+        ]);
+        let rank = |cmd: &str| rank(cmd, cmds.clone(), ShellKind::Bash);
+        assert_eq!(rank("gti status"), ["git status"]);
+        assert_eq!(rank("mkae"), ["make"]);
+        assert_eq!(rank("sl"), ["ls -l"]);
+        assert_eq!(rank("kubeclt get pods"), ["kubectl get pods"]);
+        assert!(rank("xyz").is_empty());
+    }
+}
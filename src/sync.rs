@@ -0,0 +1,155 @@
+use crate::{
+    cli::ShellKind,
+    db::{self, Record},
+    history_cleaner,
+    import::{self, Item},
+    normalize, retention,
+    settings::Settings,
+    shell,
+    shell_history::{self, Entry},
+};
+use std::{collections::HashSet, fs};
+
+const DEFAULT_LIMIT: i64 = 1000;
+
+fn key(command: &str) -> String {
+    normalize::normalize(&db::sanitize(command))
+}
+
+// HISTFILE entries the database has never seen. Commands deleted here, whose
+// name is neither a program nor one of the `known` shell names, or that
+// retention would prune right away are left out, so a sync does not bring
+// back what was removed on purpose.
+fn unrecorded(
+    entries: &[Entry],
+    recorded: &HashSet<String>,
+    floor: Option<i64>,
+    known: &[String],
+) -> Vec<Item> {
+    let forgotten = history_cleaner::forgotten();
+    entries
+        .iter()
+        .filter(|e| !recorded.contains(&key(&e.command)))
+        .filter(|e| {
+            history_cleaner::fingerprint(&e.command).is_none_or(|f| !forgotten.contains(&f))
+        })
+        .filter(|e| floor.is_none_or(|c| e.when.is_none_or(|w| w >= c)))
+        .filter(|e| shell::found_later(&e.command, ShellKind::Bash, known))
+        .map(|e| Item {
+            command: e.command.clone(),
+            when: e.when,
+            ..Default::default()
+        })
+        .collect()
+}
+
+// the most recent database commands HISTFILE lacks, oldest first
+fn missing(records: &[Record], present: &HashSet<String>, limit: i64) -> Vec<Entry> {
+    let mut recent: Vec<&Record> = records.iter().collect();
+    recent.sort_by_key(|r| -r.when_run);
+    let mut entries: Vec<Entry> = recent
+        .into_iter()
+        .take(limit as usize)
+        .filter(|r| !present.contains(&r.normalized))
+        .map(|r| Entry {
+            when: Some(r.when_run),
+            command: r.original.clone(),
+        })
+        .collect();
+    entries.reverse();
+    entries
+}
+
+pub async fn run(settings: &Settings) {
+    let Some(path) = shell_history::history_file_path() else {
+        eprintln!("HISTFILE is not set or does not exist");
+        return;
+    };
+    let contents = match fs::read(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return;
+        }
+    };
+    let limit = settings.limit.unwrap_or(DEFAULT_LIMIT);
+    let entries = shell_history::parse_entries(&String::from_utf8_lossy(&contents));
+    let present: HashSet<String> = entries.iter().map(|e| key(&e.command)).collect();
+    let keys: Vec<String> = present.iter().cloned().collect();
+
+    let queried = async {
+        let records = db::recent_records(limit).await?;
+        let recorded = db::stored_keys(&keys).await?;
+        let floor = retention::floor().await?;
+        Ok::<_, sqlx::Error>((records, recorded, floor))
+    };
+    let (records, recorded, floor) = match queried.await {
+        Ok(q) => q,
+        Err(e) => {
+            eprintln!("sync-histfile: {e}");
+            return;
+        }
+    };
+
+    let incoming = import::collapse(
+        unrecorded(&entries, &recorded, floor, &settings.known),
+        import::modified_secs(&path),
+    );
+    let outgoing = missing(&records, &present, limit);
+
+    let shown = path.display();
+    if settings.dry_run {
+        println!(
+            "{shown}: would add {} commands, would record {} commands from it",
+            outgoing.len(),
+            incoming.len()
+        );
+        return;
+    }
+    if !outgoing.is_empty() {
+        if let Err(e) = shell_history::append_entries(&path, &outgoing) {
+            eprintln!("{shown}: {e}");
+            return;
+        }
+    }
+    let done = db::upsert_records(&incoming).await;
+    println!(
+        "{shown}: added {} commands, recorded {done} commands from it",
+        outgoing.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(original: &str, when_run: i64) -> Record {
+        Record {
+            original: original.into(),
+            normalized: normalize::normalize(original),
+            cnt: 1,
+            when_run,
+            exit_code: 0,
+            cwd: None,
+            duration_ms: None,
+            variants: vec![],
+        }
+    }
+
+    #[test]
+    fn missing_recent_first_written_oldest_first() {
+        let records = [
+            record("make", 30),
+            record("ls -l -a", 20),
+            record("git st", 10),
+            record("old", 1),
+        ];
+        let present = HashSet::from([key("ls -a -l")]);
+        let entries = missing(&records, &present, 3);
+        let got: Vec<_> = entries
+            .iter()
+            .map(|e| (e.when, e.command.as_str()))
+            .collect();
+        assert_eq!(got, [(Some(10), "git st"), (Some(30), "make")]);
+    }
+}